@@ -59,7 +59,29 @@ fn main() {
         return;
     };
 
-    let _ = generate_vector_macro(&gen_dirs, &generated_mcus);
+    let Ok(interrupts) = read_interrupts(&generated_mcus) else {
+        return;
+    };
+
+    let Ok(atdfs) = read_atdfs(&selected_mcus) else {
+        return;
+    };
+
+    let _ = generate_vector_macro(&gen_dirs, &interrupts, &atdfs);
+    let _ = generate_interrupt_tables(&gen_dirs, &interrupts);
+    let _ = generate_memory_layout(&gen_dirs, &atdfs);
+    let _ = generate_reset_flags(&gen_dirs, &atdfs);
+    let _ = generate_signatures(&gen_dirs, &atdfs);
+    let _ = generate_fuses(&gen_dirs, &atdfs);
+    let _ = generate_ccp(&gen_dirs, &atdfs);
+    let _ = generate_eeprom(&gen_dirs, &atdfs);
+    let _ = generate_flash(&gen_dirs, &atdfs);
+    let _ = generate_sleep(&gen_dirs, &atdfs);
+    let _ = generate_watchdog(&gen_dirs, &atdfs);
+    let _ = generate_register_file(&gen_dirs, &atdfs);
+    let _ = generate_interrupt_bits(&gen_dirs, &atdfs);
+    let _ = generate_cpuint(&gen_dirs, &atdfs);
+    let _ = generate_selected_module(&gen_dirs, &selected_mcus);
 }
 
 fn get_create_gen_dirs() -> Result<GenerationDirs, std::io::Error> {
@@ -272,6 +294,9 @@ fn build_mcu_modules(
             *item = syn::parse_quote! {use super::DEVICE_PERIPHERALS;};
             break;
         }
+        if host_mock() {
+            mock_peripheral_addresses(&mut syntax_tree.items, mcu);
+        }
 
         let formatted = prettyplease::unparse(&syntax_tree);
         if let Err(e) = fs::write(&module_path, &formatted) {
@@ -286,13 +311,313 @@ fn build_mcu_modules(
     Ok(map)
 }
 
-fn generate_vector_macro(
-    gen_dirs: &GenerationDirs,
+/// Returns whether peripherals are backed by the simulated register file of the `host-mock`
+/// feature.  On AVR, the feature has no effect.
+fn host_mock() -> bool {
+    env::var_os("CARGO_FEATURE_HOST_MOCK").is_some()
+        && env::var("CARGO_CFG_TARGET_ARCH").as_deref() != Ok("avr")
+}
+
+/// Points svd2rust's `PTR` constants of all peripherals into the chip's `REGISTER_FILE` instead
+/// of the fixed I/O addresses.
+fn mock_peripheral_addresses(items: &mut [syn::Item], mcu: &str) {
+    let register_file: syn::Path =
+        syn::parse_str(&format!("crate::devices::{}::REGISTER_FILE", mcu)).unwrap();
+    for item in items {
+        match item {
+            syn::Item::Impl(implementation) => {
+                for impl_item in implementation.items.iter_mut() {
+                    let syn::ImplItem::Const(constant) = impl_item else {
+                        continue;
+                    };
+                    if constant.ident != "PTR" {
+                        continue;
+                    }
+                    let syn::Expr::Cast(syn::ExprCast { expr, .. }) = &constant.expr else {
+                        continue;
+                    };
+                    constant.expr = syn::parse_quote! { #register_file.ptr(#expr) };
+                }
+            }
+            syn::Item::Mod(syn::ItemMod {
+                content: Some((_, items)),
+                ..
+            }) => mock_peripheral_addresses(items, mcu),
+            _ => {}
+        }
+    }
+}
+
+fn read_atdfs(
+    mcus: &BTreeMap<String, McuInputs>,
+) -> Result<BTreeMap<String, xmltree::Element>, ()> {
+    let mut map = BTreeMap::new();
+    for (mcu, inputs) in mcus {
+        let atdf_reader = File::open(&inputs.atdf_path).unwrap();
+        let atdf = match xmltree::Element::parse(atdf_reader) {
+            Ok(a) => a,
+            Err(e) => {
+                println!("cargo::error=Failed to parse ATDF for {}!\n{}", mcu, e);
+                return Err(());
+            }
+        };
+        map.insert(mcu.to_owned(), atdf);
+    }
+    Ok(map)
+}
+
+/// Iterates over all child elements of `element` named `name`.
+fn child_elements<'a>(
+    element: &'a xmltree::Element,
+    name: &'a str,
+) -> impl Iterator<Item = &'a xmltree::Element> {
+    element
+        .children
+        .iter()
+        .filter_map(|node| node.as_element())
+        .filter(move |e| e.name == name)
+}
+
+/// Parses an ATDF attribute holding either a decimal or a hexadecimal number.
+fn attribute_u32(element: &xmltree::Element, name: &str) -> Option<u32> {
+    let value = element.attributes.get(name)?;
+    match value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+    {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => value.parse().ok(),
+    }
+}
+
+/// Returns the `<device>` element of a parsed ATDF file.
+fn atdf_device(atdf: &xmltree::Element) -> Option<&xmltree::Element> {
+    atdf.get_child("devices")?.get_child("device")
+}
+
+/// Returns the `<address-space>` with the given id (e.g. `prog`, `data`, `eeprom`).
+fn address_space<'a>(atdf: &'a xmltree::Element, id: &str) -> Option<&'a xmltree::Element> {
+    child_elements(
+        atdf_device(atdf)?.get_child("address-spaces")?,
+        "address-space",
+    )
+    .find(|space| space.attributes.get("id").map(String::as_str) == Some(id))
+}
+
+/// Returns the first `<memory-segment>` of the given type (e.g. `flash`, `ram`, `eeprom`) and the
+/// id of the address space containing it.
+fn memory_segment<'a>(
+    atdf: &'a xmltree::Element,
+    kind: &str,
+) -> Option<(&'a str, &'a xmltree::Element)> {
+    child_elements(
+        atdf_device(atdf)?.get_child("address-spaces")?,
+        "address-space",
+    )
+    .flat_map(|space| {
+        let id = space.attributes.get("id").map(String::as_str).unwrap_or("");
+        child_elements(space, "memory-segment").map(move |segment| (id, segment))
+    })
+    .find(|(_, segment)| segment.attributes.get("type").map(String::as_str) == Some(kind))
+}
+
+/// Returns the value of a `<property>` in the device's `<property-group>` named `group`.
+fn property(atdf: &xmltree::Element, group: &str, name: &str) -> Option<u32> {
+    let group = child_elements(
+        atdf_device(atdf)?.get_child("property-groups")?,
+        "property-group",
+    )
+    .find(|g| g.attributes.get("name").map(String::as_str) == Some(group))?;
+    child_elements(group, "property")
+        .find(|p| p.attributes.get("name").map(String::as_str) == Some(name))
+        .and_then(|p| attribute_u32(p, "value"))
+}
+
+/// Looks up a register of a peripheral module and returns its address in the data space together
+/// with the masks of its bitfields.
+fn find_register(
+    atdf: &xmltree::Element,
+    module: &str,
+    register: &str,
+) -> Option<(u32, BTreeMap<String, u32>)> {
+    let has_name = |e: &&xmltree::Element, attribute: &str, name: &str| {
+        e.attributes.get(attribute).map(String::as_str) == Some(name)
+    };
+
+    let instance_group = child_elements(atdf_device(atdf)?.get_child("peripherals")?, "module")
+        .filter(|m| has_name(m, "name", module))
+        .flat_map(|m| child_elements(m, "instance"))
+        .flat_map(|i| child_elements(i, "register-group"))
+        .find(|g| has_name(g, "name-in-module", module))?;
+    let register = child_elements(atdf.get_child("modules")?, "module")
+        .filter(|m| has_name(m, "name", module))
+        .flat_map(|m| child_elements(m, "register-group"))
+        .filter(|g| has_name(g, "name", module))
+        .flat_map(|g| child_elements(g, "register"))
+        .find(|r| has_name(r, "name", register))?;
+
+    let address = attribute_u32(instance_group, "offset")? + attribute_u32(register, "offset")?;
+    let bitfields = child_elements(register, "bitfield")
+        .filter_map(|b| {
+            Some((
+                b.attributes.get("name")?.to_owned(),
+                attribute_u32(b, "mask")?,
+            ))
+        })
+        .collect();
+    Some((address, bitfields))
+}
+
+/// Returns the values of a `<value-group>` of a module, by name.
+fn value_group(
+    atdf: &xmltree::Element,
+    module: &str,
+    group: &str,
+) -> Option<BTreeMap<String, u32>> {
+    let group = child_elements(atdf.get_child("modules")?, "module")
+        .filter(|m| m.attributes.get("name").map(String::as_str) == Some(module))
+        .flat_map(|m| child_elements(m, "value-group"))
+        .find(|g| g.attributes.get("name").map(String::as_str) == Some(group))?;
+    Some(
+        child_elements(group, "value")
+            .filter_map(|v| {
+                Some((
+                    v.attributes.get("name")?.to_owned(),
+                    attribute_u32(v, "value")?,
+                ))
+            })
+            .collect(),
+    )
+}
+
+fn bitfield_values(
+    atdf: &xmltree::Element,
+    module: &str,
+    register: &str,
+    bitfield: &str,
+) -> Option<(u32, BTreeMap<String, u32>)> {
+    let has_name = |e: &&xmltree::Element, name: &str| {
+        e.attributes.get("name").map(String::as_str) == Some(name)
+    };
+    let bitfield = child_elements(atdf.get_child("modules")?, "module")
+        .filter(|m| has_name(m, module))
+        .flat_map(|m| child_elements(m, "register-group"))
+        .flat_map(|g| child_elements(g, "register"))
+        .filter(|r| has_name(r, register))
+        .flat_map(|r| child_elements(r, "bitfield"))
+        .find(|b| has_name(b, bitfield))?;
+    Some((
+        attribute_u32(bitfield, "mask")?,
+        value_group(atdf, module, bitfield.attributes.get("values")?)?,
+    ))
+}
+
+/// A register of a peripheral instance, at its address in the data space.
+struct AtdfRegister {
+    instance: String,
+    name: String,
+    address: u32,
+    /// Name, mask and caption of each bitfield.
+    bitfields: Vec<(String, u32, String)>,
+}
+
+/// Returns the registers of all peripheral instances of a chip.
+fn atdf_registers(atdf: &xmltree::Element) -> Vec<AtdfRegister> {
+    fn name(e: &xmltree::Element, attribute: &str) -> String {
+        e.attributes.get(attribute).cloned().unwrap_or_default()
+    }
+    fn collect(
+        module: &xmltree::Element,
+        group: &str,
+        base: u32,
+        instance: &str,
+        registers: &mut Vec<AtdfRegister>,
+    ) {
+        for group in child_elements(module, "register-group").filter(|g| name(g, "name") == group) {
+            for register in child_elements(group, "register") {
+                let Some(offset) = attribute_u32(register, "offset") else {
+                    continue;
+                };
+                registers.push(AtdfRegister {
+                    instance: instance.to_owned(),
+                    name: name(register, "name"),
+                    address: base + offset,
+                    bitfields: child_elements(register, "bitfield")
+                        .filter_map(|b| {
+                            Some((
+                                name(b, "name"),
+                                attribute_u32(b, "mask")?,
+                                name(b, "caption"),
+                            ))
+                        })
+                        .collect(),
+                });
+            }
+            // Unions like the single and split modes of `TCA` refer to further groups.
+            for nested in child_elements(group, "register-group") {
+                if let Some(offset) = attribute_u32(nested, "offset") {
+                    collect(
+                        module,
+                        &name(nested, "name-in-module"),
+                        base + offset,
+                        instance,
+                        registers,
+                    );
+                }
+            }
+        }
+    }
+
+    let mut registers = Vec::new();
+    let (Some(peripherals), Some(modules)) = (
+        atdf_device(atdf).and_then(|d| d.get_child("peripherals")),
+        atdf.get_child("modules"),
+    ) else {
+        return registers;
+    };
+    for module in child_elements(peripherals, "module") {
+        let Some(definition) =
+            child_elements(modules, "module").find(|m| name(m, "name") == name(module, "name"))
+        else {
+            continue;
+        };
+        for instance in child_elements(module, "instance") {
+            for group in child_elements(instance, "register-group") {
+                if !matches!(
+                    group.attributes.get("address-space").map(String::as_str),
+                    None | Some("data")
+                ) {
+                    continue;
+                }
+                let Some(offset) = attribute_u32(group, "offset") else {
+                    continue;
+                };
+                collect(
+                    definition,
+                    &name(group, "name-in-module"),
+                    offset,
+                    &name(instance, "name"),
+                    &mut registers,
+                );
+            }
+        }
+    }
+    registers
+}
+
+struct InterruptInfo {
+    pub name: String,
+    pub vector: u32,
+    pub peripheral: String,
+    pub description: String,
+}
+
+fn read_interrupts(
     mcus: &BTreeMap<String, PathBuf>,
-) -> Result<(), ()> {
-    let mut devices = BTreeMap::new();
+) -> Result<BTreeMap<String, Vec<InterruptInfo>>, ()> {
+    let mut map = BTreeMap::new();
     for (mcu, patched_path) in mcus {
-        let d = match svdtools::common::svd_reader::device(&patched_path) {
+        let device = match svdtools::common::svd_reader::device(patched_path) {
             Ok(d) => d,
             Err(e) => {
                 println!(
@@ -302,24 +627,100 @@ fn generate_vector_macro(
                 return Err(());
             }
         };
-        devices.insert(mcu.to_owned(), d);
+
+        let mut interrupts: Vec<InterruptInfo> = Vec::new();
+        for p in &device.peripherals {
+            for i in &p.interrupt {
+                if interrupts.iter().any(|known| known.vector == i.value) {
+                    continue;
+                }
+                interrupts.push(InterruptInfo {
+                    name: i.name.clone(),
+                    vector: i.value,
+                    peripheral: p.name.clone(),
+                    description: i.description.clone().unwrap_or_default(),
+                });
+            }
+        }
+        interrupts.sort_by_key(|i| i.vector);
+        map.insert(mcu.to_owned(), interrupts);
     }
+    Ok(map)
+}
+
+fn generate_vector_macro(
+    gen_dirs: &GenerationDirs,
+    interrupts: &BTreeMap<String, Vec<InterruptInfo>>,
+    atdfs: &BTreeMap<String, xmltree::Element>,
+) -> Result<(), ()> {
     let mut specific_matchers = Vec::new();
-    for (mcu, device) in devices {
-        for p in device.peripherals {
-            for i in &p.interrupt {
-                specific_matchers.push(format!(
-                    r#"
+    let mut asm_matchers = Vec::new();
+    for (mcu, interrupts) in interrupts {
+        // Devices with more than 8 KiB of flash have `jmp`, the others can reach their whole flash
+        // with `rjmp`.
+        let Some(flash_size) = atdfs
+            .get(mcu)
+            .and_then(|atdf| address_space(atdf, "prog"))
+            .and_then(|space| attribute_u32(space, "size"))
+        else {
+            println!("cargo::error=Failed to find flash size of {}!", mcu);
+            return Err(());
+        };
+        let jump = if flash_size > 8 * 1024 { "jmp" } else { "rjmp" };
+
+        for i in interrupts {
+            specific_matchers.push(format!(
+                r#"
         (@{0}, {1}, $it:item) => {{
             #[export_name = "__vector_{2}"]
             $it
         }};"#,
-                    mcu, i.name, i.value,
-                ));
-            }
+                mcu, i.name, i.vector,
+            ));
+            asm_matchers.push(format!(
+                r#"
+    (@{0}, {1}, $($kind:tt)*) => {{
+        $crate::__avr_device_asm_vector!(@@ "__vector_{2}", {3}, $($kind)*);
+    }};"#,
+                mcu, i.name, i.vector, jump,
+            ));
         }
     }
 
+    // Without an explicit chip argument, the interrupt is looked up in the vector table of the
+    // selected MCU.  This is only unambiguous if exactly one MCU is selected.
+    let (default_matcher, default_asm_matcher) =
+        match interrupts.keys().collect::<Vec<_>>().as_slice() {
+            [mcu] => (
+                format!(
+                    r#"
+    ($name:ident, $it:item) => {{
+        $crate::__avr_device_trampoline!(@{0}, $name, $it);
+    }};"#,
+                    mcu,
+                ),
+                format!(
+                    r#"
+    ($name:ident, $($kind:tt)*) => {{
+        $crate::__avr_device_asm_vector!(@{0}, $name, $($kind)*);
+    }};"#,
+                    mcu,
+                ),
+            ),
+            _ => (
+                r#"
+    ($name:ident, $it:item) => {
+        compile_error!(concat!("Multiple MCUs are selected, please specify the chip for interrupt ", stringify!($name), ", e.g. #[interrupt(atmega328p)]."));
+    };"#
+                .to_owned(),
+                r#"
+    ($name:ident, $($kind:tt)*) => {
+        compile_error!(concat!("Multiple MCUs are selected, please specify the chip for interrupt ", stringify!($name), ", e.g. #[interrupt(atmega328p)]."));
+    };"#
+                .to_owned(),
+            ),
+        };
+
     let mut file_handle = match File::create(&gen_dirs.module.join("vector.rs")) {
         Ok(h) => h,
         Err(e) => {
@@ -335,10 +736,44 @@ macro_rules! __avr_device_trampoline {{
     {}
     (@$mcu:ident, $name:ident, $it:item) => {{
         compile_error!(concat!("Couldn't find interrupt ", stringify!($name), ", for MCU ", stringify!($mcu), "."));
-    }}
+    }};{}
+}}
+
+/// Defines a vector in assembly: Either as a jump to the trampoline of another interrupt
+/// (`jump $target`) or as a lone `reti` (`reti`).
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __avr_device_asm_vector {{
+    (@@ $vector:literal, $jump:ident, jump $target:ident) => {{
+        ::core::arch::global_asm!(
+            concat!(".pushsection .text.", $vector, ",\"ax\",@progbits"),
+            concat!(".global ", $vector),
+            concat!(".type ", $vector, ", @function"),
+            concat!($vector, ":"),
+            concat!(stringify!($jump), " {{0}}"),
+            ".popsection",
+            sym $target,
+        );
+    }};
+    (@@ $vector:literal, $jump:ident, reti) => {{
+        ::core::arch::global_asm!(
+            concat!(".pushsection .text.", $vector, ",\"ax\",@progbits"),
+            concat!(".global ", $vector),
+            concat!(".type ", $vector, ", @function"),
+            concat!($vector, ":"),
+            "reti",
+            ".popsection",
+        );
+    }};{}
+    (@$mcu:ident, $name:ident, $($kind:tt)*) => {{
+        compile_error!(concat!("Couldn't find interrupt ", stringify!($name), ", for MCU ", stringify!($mcu), "."));
+    }};{}
 }}
 "#,
         specific_matchers.concat(),
+        default_matcher,
+        asm_matchers.concat(),
+        default_asm_matcher,
     ) {
         Ok(()) => Ok(()),
         Err(e) => {
@@ -347,3 +782,1742 @@ macro_rules! __avr_device_trampoline {{
         }
     }
 }
+
+/// Locates svd2rust's `Interrupt` enum in a generated module and returns its path relative to the
+/// module together with the variant identifiers, keyed by their discriminant (the vector number).
+fn find_interrupt_enum(file: &syn::File) -> Option<(String, BTreeMap<u32, String>)> {
+    fn variants(items: &[syn::Item]) -> Option<BTreeMap<u32, String>> {
+        let e = items.iter().find_map(|item| match item {
+            syn::Item::Enum(e) if e.ident == "Interrupt" => Some(e),
+            _ => None,
+        })?;
+        let mut map = BTreeMap::new();
+        for v in &e.variants {
+            let Some((
+                _,
+                syn::Expr::Lit(syn::ExprLit {
+                    lit: syn::Lit::Int(value),
+                    ..
+                }),
+            )) = &v.discriminant
+            else {
+                continue;
+            };
+            map.insert(value.base10_parse().ok()?, v.ident.to_string());
+        }
+        Some(map)
+    }
+
+    if let Some(map) = variants(&file.items) {
+        return Some(("Interrupt".to_owned(), map));
+    }
+    file.items.iter().find_map(|item| match item {
+        syn::Item::Mod(syn::ItemMod {
+            ident,
+            content: Some((_, items)),
+            ..
+        }) => variants(items).map(|map| (format!("{}::Interrupt", ident), map)),
+        _ => None,
+    })
+}
+
+fn read_module(gen_dirs: &GenerationDirs, mcu: &str) -> Result<syn::File, ()> {
+    let module_path = gen_dirs.module.join(mcu).with_extension("rs");
+    match fs::read_to_string(&module_path)
+        .map_err(|e| e.to_string())
+        .and_then(|s| syn::parse_file(&s).map_err(|e| e.to_string()))
+    {
+        Ok(s) => Ok(s),
+        Err(e) => {
+            println!(
+                "cargo::error=Failed to read back module file for {}!\n{}",
+                mcu, e
+            );
+            Err(())
+        }
+    }
+}
+
+fn append_to_module(gen_dirs: &GenerationDirs, mcu: &str, code: &str) -> Result<(), ()> {
+    let module_path = gen_dirs.module.join(mcu).with_extension("rs");
+    let mut file_handle = match fs::OpenOptions::new().append(true).open(&module_path) {
+        Ok(h) => h,
+        Err(e) => {
+            println!(
+                "cargo::error=Failed to open module file for {}!\n{}",
+                mcu, e
+            );
+            return Err(());
+        }
+    };
+    match file_handle.write_all(code.as_bytes()) {
+        Ok(()) => Ok(()),
+        Err(e) => {
+            println!(
+                "cargo::error=Failed to extend module file for {}!\n{}",
+                mcu, e
+            );
+            Err(())
+        }
+    }
+}
+
+fn generate_interrupt_tables(
+    gen_dirs: &GenerationDirs,
+    interrupts: &BTreeMap<String, Vec<InterruptInfo>>,
+) -> Result<(), ()> {
+    for (mcu, interrupts) in interrupts {
+        let syntax_tree = read_module(gen_dirs, mcu)?;
+        let Some((enum_path, variants)) = find_interrupt_enum(&syntax_tree) else {
+            // Chips without interrupts have no `Interrupt` enum to extend.
+            continue;
+        };
+
+        let mut entries = Vec::new();
+        let mut to_vector = Vec::new();
+        let mut from_vector = Vec::new();
+        let mut to_info = Vec::new();
+        for i in interrupts {
+            let Some(variant) = variants.get(&i.vector) else {
+                println!(
+                    "cargo::error=Interrupt {} of {} has no variant in the Interrupt enum!",
+                    i.name, mcu
+                );
+                return Err(());
+            };
+            entries.push(format!(
+                r#"
+    crate::interrupt::VectorInfo {{
+        name: {:?},
+        vector: {},
+        peripheral: {:?},
+        description: {:?},
+    }},"#,
+                i.name, i.vector, i.peripheral, i.description,
+            ));
+            to_vector.push(format!(
+                "\n            {}::{} => {},",
+                enum_path, variant, i.vector
+            ));
+            from_vector.push(format!(
+                "\n            {} => Some({}::{}),",
+                i.vector, enum_path, variant
+            ));
+            to_info.push(format!(
+                "\n            {}::{} => VECTORS[{}],",
+                enum_path,
+                variant,
+                entries.len() - 1
+            ));
+        }
+
+        let code = format!(
+            r#"
+/// Description of all interrupt vectors of this chip, ordered by vector number.
+pub const VECTORS: [crate::interrupt::VectorInfo; {0}] = [{1}
+];
+
+impl {2} {{
+    /// Returns the vector number of this interrupt.
+    #[inline]
+    pub const fn vector_number(self) -> u8 {{
+        match self {{{3}
+        }}
+    }}
+
+    /// Returns the interrupt which is served by vector number `vector`, if any.
+    #[inline]
+    pub const fn from_vector(vector: u8) -> Option<Self> {{
+        match vector {{{4}
+            _ => None,
+        }}
+    }}
+
+    /// Returns the entry of [`VECTORS`] which describes this interrupt.
+    #[inline]
+    pub const fn info(self) -> crate::interrupt::VectorInfo {{
+        match self {{{5}
+        }}
+    }}
+}}
+"#,
+            entries.len(),
+            entries.concat(),
+            enum_path,
+            to_vector.concat(),
+            from_vector.concat(),
+            to_info.concat(),
+        );
+        append_to_module(gen_dirs, mcu, &code)?;
+    }
+    Ok(())
+}
+
+fn generate_memory_layout(
+    gen_dirs: &GenerationDirs,
+    atdfs: &BTreeMap<String, xmltree::Element>,
+) -> Result<(), ()> {
+    for (mcu, atdf) in atdfs {
+        let segment = |kind| {
+            let (space, segment) = memory_segment(atdf, kind)?;
+            Some((
+                space,
+                attribute_u32(segment, "start")?,
+                attribute_u32(segment, "size")?,
+                attribute_u32(segment, "pagesize"),
+            ))
+        };
+        let (
+            Some((_, _, flash_size, Some(page_size))),
+            Some((_, sram_start, sram_size, _)),
+            Some((_, io_start, io_size, _)),
+            Some((eeprom_space, eeprom_start, eeprom_size, Some(eeprom_page_size))),
+        ) = (
+            segment("flash"),
+            segment("ram"),
+            segment("io"),
+            segment("eeprom"),
+        )
+        else {
+            println!("cargo::error=Failed to find the memory layout of {}!", mcu);
+            return Err(());
+        };
+        // Newer chips map the EEPROM into the data space, classic ones have a separate address
+        // space for it.
+        let mapped_eeprom_start = match eeprom_space {
+            "data" => format!("Some(0x{:04x})", eeprom_start),
+            _ => "None".to_owned(),
+        };
+
+        let code = format!(
+            r#"
+/// Size of the flash memory in bytes.
+pub const FLASH_SIZE: u32 = 0x{:x};
+/// Size of a flash page in bytes, i.e. the unit of flash erase and write operations.
+pub const PAGE_SIZE: u16 = 0x{:x};
+/// Start address of the internal SRAM in the data space.
+pub const SRAM_START: u16 = 0x{:04x};
+/// Size of the internal SRAM in bytes.
+pub const SRAM_SIZE: u16 = 0x{:x};
+/// Start address of the I/O registers in the data space.
+pub const IO_START: u16 = 0x{:04x};
+/// Size of the I/O register area of the data space in bytes.
+pub const IO_SIZE: u16 = 0x{:x};
+/// Size of the EEPROM in bytes.
+pub const EEPROM_SIZE: u16 = 0x{:x};
+/// Size of an EEPROM page in bytes.
+pub const EEPROM_PAGE_SIZE: u16 = 0x{:x};
+/// Start address of the EEPROM in the data space, if it is memory mapped.
+pub const MAPPED_EEPROM_START: Option<u16> = {};
+"#,
+            flash_size,
+            page_size,
+            sram_start,
+            sram_size,
+            io_start,
+            io_size,
+            eeprom_size,
+            eeprom_page_size,
+            mapped_eeprom_start,
+        );
+        append_to_module(gen_dirs, mcu, &code)?;
+    }
+    Ok(())
+}
+
+fn generate_reset_flags(
+    gen_dirs: &GenerationDirs,
+    atdfs: &BTreeMap<String, xmltree::Element>,
+) -> Result<(), ()> {
+    for (mcu, atdf) in atdfs {
+        // Classic chips report the reset cause in `CPU.MCUSR` (`CPU.MCUCSR` on older ones) and
+        // clear flags when writing zero, newer ones use `RSTCTRL.RSTFR` and clear them when
+        // writing one.
+        let register = [
+            ("CPU", "MCUSR", false),
+            ("CPU", "MCUCSR", false),
+            ("RSTCTRL", "RSTFR", true),
+        ]
+        .into_iter()
+        .find_map(|(module, register, clear_by_writing_one)| {
+            find_register(atdf, module, register).map(|(address, bitfields)| {
+                (module, register, clear_by_writing_one, address, bitfields)
+            })
+        });
+        let Some((module, register, clear_by_writing_one, address, bitfields)) = register else {
+            println!(
+                "cargo::error=Failed to find the reset flag register of {}!",
+                mcu
+            );
+            return Err(());
+        };
+
+        // Ordered like the variants of `crate::reset::ResetCause`.
+        let masks = ["PORF", "EXTRF", "BORF", "WDRF", "JTRF", "SWRF", "UPDIRF"]
+            .iter()
+            .map(|flag| format!("0x{:02x}", bitfields.get(*flag).copied().unwrap_or(0)))
+            .collect::<Vec<_>>();
+
+        let code = format!(
+            r#"
+/// Reset flag register of this chip (`{0}.{1}`), see [`crate::reset`].
+pub const RESET_FLAGS: crate::reset::ResetFlagRegister = crate::reset::ResetFlagRegister {{
+    address: 0x{2:04x},
+    clear_by_writing_one: {3},
+    masks: [{4}],
+}};
+"#,
+            module,
+            register,
+            address,
+            clear_by_writing_one,
+            masks.join(", "),
+        );
+        append_to_module(gen_dirs, mcu, &code)?;
+    }
+    Ok(())
+}
+
+fn generate_signatures(
+    gen_dirs: &GenerationDirs,
+    atdfs: &BTreeMap<String, xmltree::Element>,
+) -> Result<(), ()> {
+    for (mcu, atdf) in atdfs {
+        let signature = ["SIGNATURE0", "SIGNATURE1", "SIGNATURE2"]
+            .iter()
+            .map(|name| property(atdf, "SIGNATURES", name))
+            .collect::<Option<Vec<_>>>();
+        let Some(signature) = signature else {
+            println!("cargo::error=Failed to find the signature of {}!", mcu);
+            return Err(());
+        };
+
+        // Newer chips map the signature row into the data space.  Classic ones read it with `lpm`
+        // after an SPM command, if they support this at all.
+        let spm_control = [
+            ("BOOT_LOAD", "SPMCSR"),
+            ("CPU", "SPMCSR"),
+            ("BOOT_LOAD", "SPMCR"),
+            ("CPU", "SPMCR"),
+        ]
+        .into_iter()
+        .find_map(|(module, register)| find_register(atdf, module, register));
+        let mapped_row = address_space(atdf, "data").and_then(|space| {
+            child_elements(space, "memory-segment")
+                .find(|s| s.attributes.get("name").map(String::as_str) == Some("SIGNATURES"))
+        });
+        let row = match mapped_row {
+            Some(segment) => {
+                let Some(address) = attribute_u32(segment, "start") else {
+                    println!("cargo::error=Failed to find the signature row of {}!", mcu);
+                    return Err(());
+                };
+                format!("Mapped {{ address: 0x{:04x} }}", address)
+            }
+            _ => match spm_control {
+                Some((address, bitfields)) => {
+                    let read = ["SIGRD", "RSIG"].iter().find_map(|b| bitfields.get(*b));
+                    let enable = ["SPMEN", "SELFPRGEN"]
+                        .iter()
+                        .find_map(|b| bitfields.get(*b));
+                    match (read, enable) {
+                        (Some(read), Some(enable)) => format!(
+                            "Spm {{ spmcsr: 0x{:04x}, command: 0x{:02x} }}",
+                            address,
+                            read | enable
+                        ),
+                        _ => "Unavailable".to_owned(),
+                    }
+                }
+                None => "Unavailable".to_owned(),
+            },
+        };
+
+        let code = format!(
+            r#"
+/// Device signature of this chip.
+pub const SIGNATURE: [u8; 3] = [0x{:02x}, 0x{:02x}, 0x{:02x}];
+/// Access to the signature row of this chip, see [`crate::signature`].
+pub const SIGNATURE_ROW: crate::signature::SignatureRow = crate::signature::SignatureRow::{};
+"#,
+            signature[0], signature[1], signature[2], row,
+        );
+        append_to_module(gen_dirs, mcu, &code)?;
+    }
+    Ok(())
+}
+
+/// Converts an ATDF name like `SUT_CKSEL` to a Rust type name like `SutCksel`.  Parts which start
+/// with a digit keep the underscore in front of them, as their boundaries would be lost otherwise,
+/// e.g. `EXTXOSC_0MHZ4_0MHZ9_1KCK_14CK_65MS` becomes `Extxosc_0mhz4_0mhz9_1kck_14ck_65ms`.
+fn type_name(name: &str) -> String {
+    name.split('_')
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            let first = chars.next().unwrap();
+            let separator = if first.is_ascii_digit() { "_" } else { "" };
+            format!(
+                "{}{}{}",
+                separator,
+                first.to_ascii_uppercase(),
+                chars.as_str().to_ascii_lowercase()
+            )
+        })
+        .collect::<String>()
+}
+
+/// Turns an ATDF caption into a single line of documentation, using `fallback` if it is empty.
+fn doc_line(caption: Option<&String>, fallback: String) -> String {
+    let caption = caption
+        .map(|c| c.split_whitespace().collect::<Vec<_>>().join(" "))
+        .unwrap_or_default();
+    match caption.trim_end_matches('.') {
+        "" => fallback,
+        caption => format!("{}.", caption),
+    }
+}
+
+/// Initial values of fuse and lock bit registers which some ATDFs lack.  Classic chips come with
+/// unprogrammed lock bits, newer ones with the factory defaults of the tinyAVR 0/1-series.
+fn fallback_initval(register: &str, classic: bool) -> Option<u32> {
+    match (register, classic) {
+        ("LOCKBIT", true) => Some(0xff),
+        ("LOCKBIT", false) => Some(0xc5),
+        ("OSCCFG", false) => Some(0x02),
+        ("SYSCFG0", false) => Some(0xc4),
+        ("SYSCFG1", false) => Some(0x07),
+        ("WDTCFG" | "BODCFG" | "TCD0CFG" | "APPEND" | "BOOTEND", false) => Some(0x00),
+        _ => None,
+    }
+}
+
+/// Generates the type of a single fuse or lock bit register, together with the enums for its
+/// bitfields.  Returns the code and the size of the register in bytes.
+fn generate_fuse_register(
+    module: &xmltree::Element,
+    register: &xmltree::Element,
+    classic: bool,
+    enums: &mut HashSet<String>,
+) -> Result<(String, u32), String> {
+    let name = register
+        .attributes
+        .get("name")
+        .ok_or("register without a name")?;
+    let (size, ty, full_mask) = match attribute_u32(register, "size") {
+        Some(1) => (1, "u8", 0xff),
+        Some(2) => (2, "u16", 0xffff),
+        _ => return Err(format!("unsupported size of register {}", name)),
+    };
+    let initval = attribute_u32(register, "initval")
+        .or_else(|| fallback_initval(name, classic))
+        .ok_or_else(|| format!("no initial value for register {}", name))?;
+    let struct_name = type_name(name);
+
+    let mut code = String::new();
+    let mut setters = String::new();
+    for bitfield in child_elements(register, "bitfield") {
+        let (Some(field), Some(mask)) = (
+            bitfield.attributes.get("name"),
+            attribute_u32(bitfield, "mask"),
+        ) else {
+            return Err(format!("invalid bitfield in register {}", name));
+        };
+        let doc = doc_line(
+            bitfield.attributes.get("caption"),
+            format!("`{}` field.", field),
+        );
+        let shift = mask.trailing_zeros();
+        let shifted = |value: &str| match shift {
+            0 => value.to_owned(),
+            _ => format!("({} << {})", value, shift),
+        };
+        // Replaces the bits of the field by `value`, which must not have other bits set.
+        let insert = |value: String| match mask == full_mask {
+            true => format!("Self({})", value),
+            false => format!("Self((self.0 & !0x{:02x}) | {})", mask, value),
+        };
+        let value_group = bitfield.attributes.get("values").and_then(|values| {
+            child_elements(module, "value-group").find(|g| g.attributes.get("name") == Some(values))
+        });
+
+        let (argument, body) = match value_group {
+            Some(group) => {
+                let group_name = group.attributes.get("name").unwrap();
+                let enum_name = type_name(
+                    ["ENUM_", "FUSE_", "LOCKBIT_"]
+                        .iter()
+                        .find_map(|prefix| group_name.strip_prefix(prefix))
+                        .unwrap_or(group_name),
+                );
+                if enums.insert(enum_name.clone()) {
+                    // Some ATDFs list the same name for several encodings of a setting, only
+                    // the first one is kept.
+                    let mut variant_names = HashSet::new();
+                    let mut variants = String::new();
+                    for value in child_elements(group, "value") {
+                        let (Some(value_name), Some(bits)) =
+                            (value.attributes.get("name"), attribute_u32(value, "value"))
+                        else {
+                            return Err(format!("invalid value in value group {}", group_name));
+                        };
+                        let variant = type_name(value_name);
+                        if !variant_names.insert(variant.clone()) {
+                            continue;
+                        }
+                        variants.push_str(&format!(
+                            "\n        /// {}\n        {} = 0x{:02x},",
+                            doc_line(
+                                value.attributes.get("caption"),
+                                format!("`{}`.", value_name)
+                            ),
+                            variant,
+                            bits
+                        ));
+                    }
+                    code.push_str(&format!(
+                        r#"
+    /// {}
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    #[repr({})]
+    pub enum {} {{{}
+    }}
+"#,
+                        doc_line(group.attributes.get("caption"), doc.clone()),
+                        ty,
+                        enum_name,
+                        variants
+                    ));
+                }
+                (
+                    format!("value: {}", enum_name),
+                    insert(match shift {
+                        0 => format!("value as {}", ty),
+                        _ => format!("((value as {}) << {})", ty, shift),
+                    }),
+                )
+            }
+            None if mask.count_ones() == 1 && classic => (
+                "enable: bool".to_owned(),
+                format!(
+                    "Self(if enable {{ self.0 & !0x{0:02x} }} else {{ self.0 | 0x{0:02x} }})",
+                    mask
+                ),
+            ),
+            None if mask.count_ones() == 1 => (
+                "enable: bool".to_owned(),
+                format!(
+                    "Self(if enable {{ self.0 | 0x{0:02x} }} else {{ self.0 & !0x{0:02x} }})",
+                    mask
+                ),
+            ),
+            None if mask == full_mask => (format!("value: {}", ty), insert("value".to_owned())),
+            None => (
+                format!("value: {}", ty),
+                insert(format!("({} & 0x{:02x})", shifted("value"), mask)),
+            ),
+        };
+        setters.push_str(&format!(
+            r#"
+        /// {}
+        #[inline]
+        pub const fn {}(self, {}) -> Self {{
+            {}
+        }}
+"#,
+            doc,
+            field.to_lowercase(),
+            argument,
+            body
+        ));
+    }
+
+    code.push_str(&format!(
+        r#"
+    /// {0}
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct {1}({2});
+
+    impl {1} {{
+        /// Factory default value of this register.
+        pub const DEFAULT: Self = Self(0x{3:02x});
+
+        /// Creates the register with its factory default value.
+        #[inline]
+        pub const fn new() -> Self {{
+            Self::DEFAULT
+        }}
+
+        /// Creates the register from its raw value.
+        #[inline]
+        pub const fn from_bits(bits: {2}) -> Self {{
+            Self(bits)
+        }}
+
+        /// Raw value of the register.
+        #[inline]
+        pub const fn bits(self) -> {2} {{
+            self.0
+        }}
+{4}    }}
+
+    impl Default for {1} {{
+        #[inline]
+        fn default() -> Self {{
+            Self::DEFAULT
+        }}
+    }}
+"#,
+        doc_line(
+            register.attributes.get("caption"),
+            format!("`{}` register.", name)
+        ),
+        struct_name,
+        ty,
+        initval,
+        setters
+    ));
+    Ok((code, size))
+}
+
+fn generate_fuses(
+    gen_dirs: &GenerationDirs,
+    atdfs: &BTreeMap<String, xmltree::Element>,
+) -> Result<(), ()> {
+    for (mcu, atdf) in atdfs {
+        // Classic chips have separate address spaces for fuses and lock bits, in which bits are
+        // programmed by clearing them.  Newer chips map them into the data space.
+        let classic = address_space(atdf, "fuses").is_some();
+        let mut enums = HashSet::new();
+        let mut code = String::new();
+        for (module_name, aggregate, kind, section, emitter) in [
+            ("FUSE", "Fuses", "fuses", ".fuse", "fuses"),
+            ("LOCKBIT", "Lockbits", "lock bits", ".lock", "lockbits"),
+        ] {
+            let Some(module) = atdf.get_child("modules").and_then(|modules| {
+                child_elements(modules, "module")
+                    .find(|m| m.attributes.get("name").map(String::as_str) == Some(module_name))
+            }) else {
+                // Not all chips have lock bits.
+                continue;
+            };
+            let mut registers = child_elements(module, "register-group")
+                .flat_map(|g| child_elements(g, "register"))
+                .collect::<Vec<_>>();
+            registers.sort_by_key(|r| attribute_u32(r, "offset"));
+
+            let mut fields = String::new();
+            let mut defaults = String::new();
+            let mut bytes = String::new();
+            let mut size = 0;
+            for register in registers {
+                let (register_code, register_size) =
+                    match generate_fuse_register(module, register, classic, &mut enums) {
+                        Ok(r) => r,
+                        Err(e) => {
+                            println!(
+                                "cargo::error=Failed to generate {} of {}: {}!",
+                                kind, mcu, e
+                            );
+                            return Err(());
+                        }
+                    };
+                code.push_str(&register_code);
+
+                let name = &register.attributes["name"];
+                let field = name.to_lowercase();
+                let struct_name = type_name(name);
+                let offset = attribute_u32(register, "offset").unwrap_or(0);
+                fields.push_str(&format!(
+                    "\n        /// `{}` register.\n        pub {}: {},",
+                    name, field, struct_name
+                ));
+                defaults.push_str(&format!(
+                    "\n            {}: {}::DEFAULT,",
+                    field, struct_name
+                ));
+                // Multi-byte registers are stored little-endian.
+                for i in 0..register_size {
+                    let value = match (register_size, i) {
+                        (1, _) => format!("self.{}.bits()", field),
+                        (_, 0) => format!("self.{}.bits() as u8", field),
+                        _ => format!("(self.{}.bits() >> {}) as u8", field, i * 8),
+                    };
+                    bytes.push_str(&format!("\n            bytes[{}] = {};", offset + i, value));
+                }
+                size = size.max(offset + register_size);
+            }
+
+            code.push_str(&format!(
+                r#"
+    /// All {1} of this chip, to be placed in the `{2}` section with [`crate::{3}!`].
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct {0} {{{4}
+    }}
+
+    impl {0} {{
+        /// Factory default values of all registers.
+        pub const DEFAULT: Self = Self {{{5}
+        }};
+
+        /// Size of the `{2}` section in bytes.
+        pub const SIZE: usize = {6};
+
+        /// Returns the contents of the `{2}` section.  Unused bytes are left unprogrammed.
+        pub const fn to_bytes(self) -> [u8; Self::SIZE] {{
+            let mut bytes = [0xff; Self::SIZE];{7}
+            bytes
+        }}
+    }}
+
+    impl Default for {0} {{
+        #[inline]
+        fn default() -> Self {{
+            Self::DEFAULT
+        }}
+    }}
+"#,
+                aggregate, kind, section, emitter, fields, defaults, size, bytes
+            ));
+        }
+
+        let code = format!(
+            r#"
+/// Fuses and lock bits of this chip, see [`crate::fuses`].
+pub mod fuses {{{}}}
+"#,
+            code
+        );
+        append_to_module(gen_dirs, mcu, &code)?;
+    }
+    Ok(())
+}
+
+fn generate_ccp(
+    gen_dirs: &GenerationDirs,
+    atdfs: &BTreeMap<String, xmltree::Element>,
+) -> Result<(), ()> {
+    for (mcu, atdf) in atdfs {
+        // Only newer chips have configuration change protection.
+        let ccp = match find_register(atdf, "CPU", "CCP") {
+            Some((address, _)) => format!("Some(0x{:04x})", address),
+            None => "None".to_owned(),
+        };
+
+        let code = format!(
+            r#"
+/// Address of the configuration change protection register (`CPU.CCP`) in the data space, if this
+/// chip has one, see [`crate::ccp`].
+pub const CCP: Option<u16> = {};
+"#,
+            ccp,
+        );
+        append_to_module(gen_dirs, mcu, &code)?;
+    }
+    Ok(())
+}
+
+fn generate_eeprom(
+    gen_dirs: &GenerationDirs,
+    atdfs: &BTreeMap<String, xmltree::Element>,
+) -> Result<(), ()> {
+    for (mcu, atdf) in atdfs {
+        let classic = (
+            find_register(atdf, "EEPROM", "EECR"),
+            find_register(atdf, "EEPROM", "EEAR")
+                .or_else(|| find_register(atdf, "EEPROM", "EEARL")),
+            find_register(atdf, "EEPROM", "EEDR"),
+        );
+        let nvm = (
+            find_register(atdf, "NVMCTRL", "CTRLA"),
+            find_register(atdf, "NVMCTRL", "STATUS"),
+            value_group(atdf, "NVMCTRL", "NVMCTRL_CMD"),
+        );
+        let controller = match (classic, nvm) {
+            ((Some((eecr, bitfields)), Some((eear, _)), Some((eedr, _))), _) => {
+                // Older chips call the write enable bits `EEMWE` and `EEWE`.
+                let bit = |names: &[&str]| names.iter().find_map(|b| bitfields.get(*b)).copied();
+                match (
+                    bit(&["EEMPE", "EEMWE"]),
+                    bit(&["EEPE", "EEWE"]),
+                    bit(&["EERE"]),
+                ) {
+                    (Some(master_write_enable), Some(write_enable), Some(read_enable)) => {
+                        Some(format!(
+                            "Classic {{
+    eecr: 0x{:04x},
+    eear: 0x{:04x},
+    eedr: 0x{:04x},
+    master_write_enable: 0x{:02x},
+    write_enable: 0x{:02x},
+    read_enable: 0x{:02x},
+    programming_mode: 0x{:02x},
+}}",
+                            eecr,
+                            eear,
+                            eedr,
+                            master_write_enable,
+                            write_enable,
+                            read_enable,
+                            bit(&["EEPM"]).unwrap_or(0),
+                        ))
+                    }
+                    _ => None,
+                }
+            }
+            (_, (Some((ctrla, _)), Some((status, bitfields)), Some(commands))) => {
+                let busy = bitfields.get("EEBUSY");
+                let command = |name: &str| commands.get(name).copied();
+                // The tinyAVR 0/1 and megaAVR 0-series write a page buffer filled through the
+                // mapped EEPROM, newer chips write bytes directly after selecting a command.
+                match (busy, command("PAGEERASEWRITE"), command("EEERWR")) {
+                    (Some(busy), Some(erase_write), _) => Some(format!(
+                        "PageBuffer {{
+    ctrla: 0x{:04x},
+    status: 0x{:04x},
+    busy: 0x{:02x},
+    erase_write: 0x{:02x},
+    erase: 0x{:02x},
+    write: 0x{:02x},
+}}",
+                        ctrla,
+                        status,
+                        busy,
+                        erase_write,
+                        command("PAGEERASE").unwrap_or(erase_write),
+                        command("PAGEWRITE").unwrap_or(erase_write),
+                    )),
+                    (Some(busy), None, Some(erase_write)) => Some(format!(
+                        "Command {{
+    ctrla: 0x{:04x},
+    status: 0x{:04x},
+    busy: 0x{:02x},
+    erase_write: 0x{:02x},
+    erase: 0x{:02x},
+    write: 0x{:02x},
+    none: 0x{:02x},
+}}",
+                        ctrla,
+                        status,
+                        busy,
+                        erase_write,
+                        command("EEBER").unwrap_or(erase_write),
+                        command("EEWR").unwrap_or(erase_write),
+                        command("NONE").unwrap_or(0),
+                    )),
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+        let Some(controller) = controller else {
+            println!(
+                "cargo::error=Failed to find the EEPROM controller of {}!",
+                mcu
+            );
+            return Err(());
+        };
+
+        let code = format!(
+            r#"
+/// EEPROM controller of this chip, see [`crate::eeprom`].
+pub const EEPROM: crate::eeprom::EepromController = crate::eeprom::EepromController::{};
+"#,
+            controller,
+        );
+        append_to_module(gen_dirs, mcu, &code)?;
+    }
+    Ok(())
+}
+
+fn generate_flash(
+    gen_dirs: &GenerationDirs,
+    atdfs: &BTreeMap<String, xmltree::Element>,
+) -> Result<(), ()> {
+    for (mcu, atdf) in atdfs {
+        let spm_control = [
+            ("BOOT_LOAD", "SPMCSR"),
+            ("CPU", "SPMCSR"),
+            ("BOOT_LOAD", "SPMCR"),
+            ("CPU", "SPMCR"),
+        ]
+        .into_iter()
+        .find_map(|(module, register)| find_register(atdf, module, register));
+        let nvm = (
+            find_register(atdf, "NVMCTRL", "CTRLA"),
+            find_register(atdf, "NVMCTRL", "STATUS"),
+            value_group(atdf, "NVMCTRL", "NVMCTRL_CMD"),
+        );
+        let controller = match (spm_control, nvm) {
+            (Some((spmcsr, bitfields)), _) => {
+                let bit = |names: &[&str]| names.iter().find_map(|b| bitfields.get(*b)).copied();
+                match (
+                    bit(&["SPMEN", "SELFPRGEN"]),
+                    bit(&["PGERS"]),
+                    bit(&["PGWRT"]),
+                ) {
+                    (Some(enable), Some(page_erase), Some(page_write)) => Some(format!(
+                        "Spm {{
+    spmcsr: 0x{:04x},
+    enable: 0x{:02x},
+    page_erase: 0x{:02x},
+    page_write: 0x{:02x},
+    rww_enable: 0x{:02x},
+}}",
+                        spmcsr,
+                        enable,
+                        page_erase,
+                        page_write,
+                        bit(&["RWWSRE"]).unwrap_or(0),
+                    )),
+                    _ => None,
+                }
+            }
+            (_, (Some((ctrla, _)), Some((status, bitfields)), Some(commands))) => {
+                let command = |name: &str| commands.get(name).copied();
+                let mapped = address_space(atdf, "data")
+                    .and_then(|space| {
+                        child_elements(space, "memory-segment").find(|s| {
+                            s.attributes.get("name").map(String::as_str) == Some("MAPPED_PROGMEM")
+                        })
+                    })
+                    .and_then(|segment| attribute_u32(segment, "start"));
+                // The tinyAVR 0/1 and megaAVR 0-series write a page buffer filled through the
+                // mapped flash, newer chips write words directly after selecting a command.
+                let page_buffer = (
+                    bitfields.get("FBUSY"),
+                    mapped,
+                    command("PAGEWRITE"),
+                    command("PAGEERASE"),
+                    command("PAGEBUFCLR"),
+                );
+                let direct = (bitfields.get("FLBUSY"), command("FLWR"), command("FLPER"));
+                match (page_buffer, direct) {
+                    (
+                        (Some(busy), Some(mapped), Some(write), Some(erase), Some(clear_buffer)),
+                        _,
+                    ) => Some(format!(
+                        "PageBuffer {{
+    ctrla: 0x{:04x},
+    status: 0x{:04x},
+    busy: 0x{:02x},
+    mapped: 0x{:04x},
+    write: 0x{:02x},
+    erase: 0x{:02x},
+    clear_buffer: 0x{:02x},
+}}",
+                        ctrla, status, busy, mapped, write, erase, clear_buffer,
+                    )),
+                    (_, (Some(busy), Some(write), Some(erase))) => Some(format!(
+                        "Command {{
+    ctrla: 0x{:04x},
+    status: 0x{:04x},
+    busy: 0x{:02x},
+    write: 0x{:02x},
+    erase: 0x{:02x},
+    none: 0x{:02x},
+}}",
+                        ctrla,
+                        status,
+                        busy,
+                        write,
+                        erase,
+                        command("NONE").unwrap_or(0),
+                    )),
+                    _ => None,
+                }
+            }
+            // A few small chips can't program their own flash.
+            _ => Some("Unavailable".to_owned()),
+        };
+        let Some(controller) = controller else {
+            println!(
+                "cargo::error=Failed to find the flash controller of {}!",
+                mcu
+            );
+            return Err(());
+        };
+
+        // Classic chips have a boot section of selectable size at the end of the flash, newer
+        // ones have it at the start, sized in blocks of 256 (`BOOTEND`) or 512 (`BOOTSIZE`)
+        // bytes.
+        let mut boot_starts = address_space(atdf, "prog")
+            .into_iter()
+            .flat_map(|space| child_elements(space, "memory-segment"))
+            .filter(|s| {
+                s.attributes
+                    .get("name")
+                    .is_some_and(|name| name.starts_with("BOOT_SECTION_"))
+            })
+            .filter_map(|s| attribute_u32(s, "start"))
+            .collect::<Vec<_>>();
+        boot_starts.sort_unstable_by(|a, b| b.cmp(a));
+        let boot_section = if !boot_starts.is_empty() {
+            format!(
+                "End {{ starts: &[{}] }}",
+                boot_starts
+                    .iter()
+                    .map(|start| format!("0x{:x}", start))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        } else if find_register(atdf, "FUSE", "BOOTEND").is_some() {
+            "Start { block_size: 0x100 }".to_owned()
+        } else if find_register(atdf, "FUSE", "BOOTSIZE").is_some() {
+            "Start { block_size: 0x200 }".to_owned()
+        } else {
+            "None".to_owned()
+        };
+
+        let code = format!(
+            r#"
+/// Flash controller of this chip, see [`crate::flash`].
+pub const FLASH: crate::flash::FlashController = crate::flash::FlashController::{};
+/// Boot section of this chip, see [`crate::flash`].
+pub const BOOT_SECTION: crate::flash::BootSection = crate::flash::BootSection::{};
+"#,
+            controller, boot_section,
+        );
+        append_to_module(gen_dirs, mcu, &code)?;
+    }
+    Ok(())
+}
+
+fn generate_sleep(
+    gen_dirs: &GenerationDirs,
+    atdfs: &BTreeMap<String, xmltree::Element>,
+) -> Result<(), ()> {
+    const MODES: [(&str, &str); 6] = [
+        ("IDLE", "Idle"),
+        ("ADC", "AdcNoiseReduction"),
+        ("PDOWN", "PowerDown"),
+        ("PSAVE", "PowerSave"),
+        ("STDBY", "Standby"),
+        ("ESTDBY", "ExtendedStandby"),
+    ];
+
+    for (mcu, atdf) in atdfs {
+        // Newer chips have a sleep controller, classic ones keep the sleep mode bits in `SMCR` or
+        // `MCUCR`.  Some classic chips split the mode over several bitfields, the one with the
+        // value group gives the position of the encoded modes.
+        let controller = match find_register(atdf, "SLPCTRL", "CTRLA") {
+            Some((address, bitfields)) => bitfield_values(atdf, "SLPCTRL", "CTRLA", "SMODE")
+                .and_then(|values| Some((address, *bitfields.get("SEN")?, bitfields, values))),
+            None => ["SMCR", "MCUCR"].into_iter().find_map(|register| {
+                let (address, bitfields) = find_register(atdf, "CPU", register)?;
+                let values = ["SM", "SM2"]
+                    .into_iter()
+                    .find_map(|field| bitfield_values(atdf, "CPU", register, field))?;
+                Some((address, *bitfields.get("SE")?, bitfields, values))
+            }),
+        };
+        let Some((address, enable, bitfields, (values_mask, values))) = controller else {
+            println!(
+                "cargo::error=Failed to find the sleep controller of {}!",
+                mcu
+            );
+            return Err(());
+        };
+        let mode_mask = ["SMODE", "SM", "SM0", "SM1", "SM2"]
+            .iter()
+            .filter_map(|field| bitfields.get(*field))
+            .fold(0, |mask, field| mask | field);
+        let modes = MODES
+            .iter()
+            .filter_map(|(name, variant)| {
+                let bits = values.get(*name)? << values_mask.trailing_zeros();
+                Some(format!(
+                    "\n        (crate::power::SleepMode::{}, 0x{:02x}),",
+                    variant, bits
+                ))
+            })
+            .collect::<String>();
+
+        let bod_disable = ["MCUCR", "BODCR"]
+            .into_iter()
+            .find_map(|register| {
+                let (address, bitfields) = find_register(atdf, "CPU", register)?;
+                Some(format!(
+                    "Some(crate::power::BodDisable {{
+        register: 0x{:04x},
+        bods: 0x{:02x},
+        bods_enable: 0x{:02x},
+    }})",
+                    address,
+                    bitfields.get("BODS")?,
+                    bitfields.get("BODSE")?,
+                ))
+            })
+            .unwrap_or_else(|| "None".to_owned());
+
+        let code = format!(
+            r#"
+/// Sleep controller of this chip, see [`crate::power`].
+pub const SLEEP: crate::power::SleepController = crate::power::SleepController {{
+    register: 0x{:04x},
+    enable: 0x{:02x},
+    mode_mask: 0x{:02x},
+    modes: &[{}
+    ],
+    bod_disable: {},
+}};
+"#,
+            address, enable, mode_mask, modes, bod_disable,
+        );
+        append_to_module(gen_dirs, mcu, &code)?;
+    }
+    Ok(())
+}
+
+fn generate_watchdog(
+    gen_dirs: &GenerationDirs,
+    atdfs: &BTreeMap<String, xmltree::Element>,
+) -> Result<(), ()> {
+    const TIMEOUTS: [&str; 11] = [
+        "Ms8", "Ms16", "Ms32", "Ms64", "Ms125", "Ms250", "Ms500", "S1", "S2", "S4", "S8",
+    ];
+    // Spreads the bits of `value` over the bits set in `mask`, from the lowest to the highest.
+    let deposit = |mut value: u32, mask: u32| {
+        (0..8)
+            .filter(|bit| mask & (1 << bit) != 0)
+            .fold(0, |bits, bit| {
+                let bits = bits | (value & 1) << bit;
+                value >>= 1;
+                bits
+            })
+    };
+    let timeouts = |pairs: Vec<(&str, u32)>| {
+        pairs
+            .into_iter()
+            .map(|(timeout, bits)| {
+                format!(
+                    "\n        (crate::watchdog::Timeout::{}, 0x{:02x}),",
+                    timeout, bits
+                )
+            })
+            .collect::<String>()
+    };
+
+    for (mcu, atdf) in atdfs {
+        let classic = ["WDTCSR", "WDTCR"]
+            .into_iter()
+            .find_map(|register| find_register(atdf, "WDT", register));
+        let new = (
+            find_register(atdf, "WDT", "CTRLA"),
+            find_register(atdf, "WDT", "STATUS"),
+            value_group(atdf, "WDT", "WDT_PERIOD"),
+        );
+        let controller = match (classic, new) {
+            (Some((wdtcsr, bitfields)), _) => {
+                let bit = |names: &[&str]| names.iter().find_map(|b| bitfields.get(*b)).copied();
+                match (bit(&["WDE"]), bit(&["WDP"])) {
+                    (Some(enable), Some(prescaler)) => {
+                        // Chips with a 3 bit prescaler run the watchdog from a 1 MHz oscillator,
+                        // the others from a 128 kHz (or 32 kHz) one.  Both start at about 16 ms
+                        // and double the timeout with each step.
+                        let steps = match prescaler.count_ones() {
+                            3 => 8,
+                            _ => 10,
+                        };
+                        let pairs = (0..steps)
+                            .map(|step| (TIMEOUTS[1 + step], deposit(step as u32, prescaler)))
+                            .collect();
+                        Some(format!(
+                            "Classic {{
+    wdtcsr: 0x{:04x},
+    change_enable: 0x{:02x},
+    enable: 0x{:02x},
+    interrupt_enable: 0x{:02x},
+    interrupt_flag: 0x{:02x},
+    prescaler: 0x{:02x},
+    timeouts: &[{}
+    ],
+}}",
+                            wdtcsr,
+                            bit(&["WDCE", "WDTOE"]).unwrap_or(0),
+                            enable,
+                            bit(&["WDIE", "WDTIE"]).unwrap_or(0),
+                            bit(&["WDIF", "WDTIF"]).unwrap_or(0),
+                            prescaler,
+                            timeouts(pairs),
+                        ))
+                    }
+                    _ => None,
+                }
+            }
+            (_, (Some((ctrla, _)), Some((status, bitfields)), Some(periods))) => {
+                match bitfields.get("SYNCBUSY") {
+                    Some(busy) => {
+                        // Periods run from 8 cycles of the 1.024 kHz oscillator (about 8 ms) up.
+                        let pairs = (1..=TIMEOUTS.len() as u32)
+                            .filter(|value| periods.values().any(|v| v == value))
+                            .map(|value| (TIMEOUTS[value as usize - 1], value))
+                            .collect();
+                        Some(format!(
+                            "Ctrla {{
+    ctrla: 0x{:04x},
+    status: 0x{:04x},
+    busy: 0x{:02x},
+    timeouts: &[{}
+    ],
+}}",
+                            ctrla,
+                            status,
+                            busy,
+                            timeouts(pairs),
+                        ))
+                    }
+                    None => None,
+                }
+            }
+            _ => None,
+        };
+        let Some(controller) = controller else {
+            println!("cargo::error=Failed to find the watchdog of {}!", mcu);
+            return Err(());
+        };
+
+        let code = format!(
+            r#"
+/// Watchdog timer of this chip, see [`crate::watchdog`].
+pub const WATCHDOG: crate::watchdog::WatchdogController = crate::watchdog::WatchdogController::{};
+"#,
+            controller,
+        );
+        append_to_module(gen_dirs, mcu, &code)?;
+    }
+    Ok(())
+}
+
+fn generate_register_file(
+    gen_dirs: &GenerationDirs,
+    atdfs: &BTreeMap<String, xmltree::Element>,
+) -> Result<(), ()> {
+    if !host_mock() {
+        return Ok(());
+    }
+    for (mcu, atdf) in atdfs {
+        // The data space covers all registers, including the extended I/O space and the
+        // memory mapped peripherals of newer chips.
+        let Some(size) = address_space(atdf, "data").and_then(|space| attribute_u32(space, "size"))
+        else {
+            println!(
+                "cargo::error=Failed to find the data space size of {}!",
+                mcu
+            );
+            return Err(());
+        };
+        let code = format!(
+            r#"
+/// Simulated registers of this chip, which back its peripherals, see [`crate::mock`].
+pub static REGISTER_FILE: crate::mock::RegisterFile<0x{:x}> = crate::mock::RegisterFile::new();
+"#,
+            size,
+        );
+        append_to_module(gen_dirs, mcu, &code)?;
+    }
+    Ok(())
+}
+
+// Names of the bitfields which enable an interrupt of a classic chip, optionally with the index
+// of the bit in a bitfield which covers multiple interrupts, like `INT` in `EIMSK`.
+fn classic_enable_fields(interrupt: &str) -> Vec<(String, Option<u32>)> {
+    let interrupt = interrupt.replace("EXT_INT", "INT");
+    let (head, event) = interrupt.split_once('_').unwrap_or((&interrupt, ""));
+    let peripheral = head.trim_end_matches(|c: char| c.is_ascii_digit());
+    let n = &head[peripheral.len()..];
+    let index = n.parse().ok();
+    let fields: &[&str] = match (peripheral, event) {
+        ("INT", "") => return vec![(format!("INT{}", n), None), ("INT".to_owned(), index)],
+        ("PCINT", "") => {
+            return vec![
+                (format!("PCIE{}", n), None),
+                ("PCIE".to_owned(), index.or(Some(0))),
+            ]
+        }
+        ("TIMER" | "TIM", "OVF") => &["TOIE{}"],
+        ("TIMER" | "TIM", "COMP" | "CMP") => &["OCIE{}"],
+        ("TIMER" | "TIM", "CAPT") => &["ICIE{}", "TICIE{}"],
+        ("TIMER" | "TIM", event) if event.starts_with("COMP") || event.starts_with("CMP") => {
+            let channel = event.trim_start_matches("COMP").trim_start_matches("CMP");
+            return vec![(format!("OCIE{}{}", n, channel), None)];
+        }
+        ("USART", "RX" | "RXC") => &["RXCIE{}", "RXCIE"],
+        ("USART", "UDRE") => &["UDRIE{}", "UDRIE"],
+        ("USART", "TX" | "TXC") => &["TXCIE{}", "TXCIE"],
+        ("SPI", "STC" | "") => &["SPIE{}", "SPIE"],
+        ("ADC", "") => &["ADIE"],
+        ("EE" | "EEPROM", "READY" | "RDY" | "Ready") => &["EERIE"],
+        ("ANALOG" | "ANA", "COMP") => &["ACIE"],
+        ("ANA", event) if event.starts_with("COMP") => {
+            return vec![(format!("ACIE{}", event.trim_start_matches("COMP")), None)];
+        }
+        ("TWI", "") => &["TWIE{}", "TWIE"],
+        ("SPM", "READY" | "RDY" | "Ready") => &["SPMIE"],
+        ("WDT", "" | "OVERFLOW") => &["WDIE", "WDTIE"],
+        ("USI", "START" | "STR" | "STRT") => &["USISIE"],
+        ("USI", "OVF" | "OVERFLOW") => &["USIOIE"],
+        _ => &[],
+    };
+    // Chips with a single instance of a peripheral often still number its bitfields, like
+    // `RXCIE0` for the `USART_RX` interrupt.
+    let numbers: &[&str] = match n {
+        "" => &["", "0"],
+        n => &[n],
+    };
+    fields
+        .iter()
+        .flat_map(|field| numbers.iter().map(move |n| (field.replace("{}", n), None)))
+        .collect()
+}
+
+// Names of the flags which signal that an interrupt enabled by the bitfield `enable` of a classic
+// chip is pending.
+fn classic_flag_field(enable: &str) -> String {
+    const PREFIXES: &[(&str, &str)] = &[
+        ("TOIE", "TOV"),
+        ("OCIE", "OCF"),
+        ("TICIE", "ICF"),
+        ("ICIE", "ICF"),
+        ("RXCIE", "RXC"),
+        ("UDRIE", "UDRE"),
+        ("TXCIE", "TXC"),
+        ("ACIE", "ACI"),
+        ("TWIE", "TWINT"),
+        ("INT", "INTF"),
+    ];
+    PREFIXES
+        .iter()
+        .find_map(|(prefix, flag)| Some(format!("{}{}", flag, enable.strip_prefix(prefix)?)))
+        // Otherwise `IE` becomes `IF`, like `PCIE0` and `PCIF0`.
+        .unwrap_or_else(|| enable.replacen("IE", "IF", 1))
+}
+
+// Finds the register which has the bitfield `field`, or the one of `instance` if there are
+// several.  Returns the register and the mask of the bitfield, or of its `index`th bit.
+fn find_bitfield<'a>(
+    registers: &'a [AtdfRegister],
+    instance: &str,
+    field: &str,
+    index: Option<u32>,
+) -> Option<(&'a AtdfRegister, u32)> {
+    let candidates = registers
+        .iter()
+        .filter_map(|r| Some((r, r.bitfields.iter().find(|b| b.0 == field)?.1)))
+        .collect::<Vec<_>>();
+    let (register, mask) = match candidates.as_slice() {
+        [candidate] => *candidate,
+        _ => match candidates
+            .iter()
+            .filter(|(r, _)| r.instance == instance)
+            .collect::<Vec<_>>()
+            .as_slice()
+        {
+            [candidate] => **candidate,
+            _ => return None,
+        },
+    };
+    match index {
+        Some(index) => {
+            let bit = (0..32)
+                .map(|bit| 1 << bit)
+                .filter(|bit| mask & bit != 0)
+                .nth(index as usize)?;
+            Some((register, bit))
+        }
+        None => Some((register, mask)),
+    }
+}
+
+// Returns the only register and mask found, allowing for registers which appear once per mode of
+// a union at the same address.
+fn unique_bits(found: Vec<(&AtdfRegister, u32)>) -> Option<(&AtdfRegister, u32)> {
+    match found.as_slice() {
+        [(register, mask), rest @ ..]
+            if rest
+                .iter()
+                .all(|(r, m)| r.address == register.address && m == mask) =>
+        {
+            Some((*register, *mask))
+        }
+        _ => None,
+    }
+}
+
+// Finds the bits which enable the interrupt `name` of the peripheral `instance` on newer chips,
+// which have an `INTCTRL` register per peripheral.
+fn instance_enable<'a>(
+    registers: &'a [AtdfRegister],
+    instance: &str,
+    name: &str,
+    names: &[&str],
+) -> Option<(&'a AtdfRegister, u32)> {
+    fn direct<'a>(registers: &[&'a AtdfRegister], name: &str) -> Option<(&'a AtdfRegister, u32)> {
+        let field = |field: &str, intctrl: bool| {
+            registers
+                .iter()
+                .filter(|r| !intctrl || r.name.contains("INTCTRL"))
+                .filter_map(|r| Some((*r, r.bitfields.iter().find(|b| b.0 == field)?.1)))
+                .collect()
+        };
+        // A bitfield named after the interrupt in `INTCTRL`, like `TCA.INTCTRL.OVF`, ...
+        unique_bits(field(name, true))
+            // ... or with an `IE` suffix elsewhere, like `USART.CTRLA.RXCIE`, ...
+            .or_else(|| unique_bits(field(&format!("{}IE", name), false)))
+            // ... or a whole register named after the interrupt, like `RTC.PITINTCTRL`.
+            .or_else(|| {
+                let register = format!("{}INTCTRL", name);
+                unique_bits(
+                    registers
+                        .iter()
+                        .filter(|r| r.name == register)
+                        .map(|r| (*r, r.bitfields.iter().fold(0, |mask, b| mask | b.1)))
+                        .collect(),
+                )
+            })
+    }
+
+    let registers = registers
+        .iter()
+        .filter(|r| r.instance == instance)
+        .collect::<Vec<_>>();
+    if let Some(found) = direct(&registers, name) {
+        return Some(found);
+    }
+
+    // The only interrupt without a direct match gets the remaining bits of the `INTCTRL`
+    // register, like `CAPT` and `OVF` for the `INT` interrupt of `TCB`.  Only single bits
+    // count, as wider bitfields select a mode instead, like `INTMODEn` of `CCL`.
+    let mut claimed = BTreeMap::<u32, u32>::new();
+    for other in names.iter().filter(|other| **other != name) {
+        let (register, mask) = direct(&registers, other)?;
+        *claimed.entry(register.address).or_default() |= mask;
+    }
+    let remaining = registers
+        .iter()
+        .filter(|r| r.name.contains("INTCTRL"))
+        .map(|r| {
+            let claimed = claimed.get(&r.address).copied().unwrap_or(0);
+            let mask = r
+                .bitfields
+                .iter()
+                .filter(|b| b.1 & claimed == 0 && b.1.count_ones() == 1)
+                .fold(0, |mask, b| mask | b.1);
+            (*r, mask)
+        })
+        .filter(|(_, mask)| *mask != 0)
+        .collect::<Vec<_>>();
+    match remaining.as_slice() {
+        [found] => Some(*found),
+        _ => None,
+    }
+}
+
+// Finds the flags which signal that an interrupt enabled by `mask` of `enable` is pending on newer
+// chips, like `TCA.INTFLAGS.OVF` for `TCA.INTCTRL.OVF` or `USART.STATUS.RXCIF` for
+// `USART.CTRLA.RXCIE`.
+fn instance_flags<'a>(
+    registers: &'a [AtdfRegister],
+    enable: &AtdfRegister,
+    mask: u32,
+) -> Option<(&'a AtdfRegister, u32)> {
+    let mut found: Option<(&AtdfRegister, u32)> = None;
+    for (name, _, _) in enable.bitfields.iter().filter(|b| b.1 & mask != 0) {
+        let names = [
+            name.clone(),
+            format!("{}IF", name.strip_suffix("IE").unwrap_or(name)),
+        ];
+        let (register, flag) = unique_bits(
+            registers
+                .iter()
+                .filter(|r| r.instance == enable.instance && r.address != enable.address)
+                .filter(|r| r.name.contains("FLAGS") || r.name.contains("STATUS"))
+                .filter_map(|r| Some((r, r.bitfields.iter().find(|b| names.contains(&b.0))?.1)))
+                .collect(),
+        )?;
+        found = match found {
+            None => Some((register, flag)),
+            Some((r, flags)) if r.address == register.address => Some((r, flags | flag)),
+            Some(_) => return None,
+        };
+    }
+    found
+}
+
+fn generate_interrupt_bits(
+    gen_dirs: &GenerationDirs,
+    atdfs: &BTreeMap<String, xmltree::Element>,
+) -> Result<(), ()> {
+    for (mcu, atdf) in atdfs {
+        let syntax_tree = read_module(gen_dirs, mcu)?;
+        let Some((enum_path, variants)) = find_interrupt_enum(&syntax_tree) else {
+            continue;
+        };
+        let registers = atdf_registers(atdf);
+        let interrupts = atdf_device(atdf)
+            .and_then(|d| d.get_child("interrupts"))
+            .map(|i| child_elements(i, "interrupt").collect::<Vec<_>>())
+            .unwrap_or_default();
+        let attribute = |e: &xmltree::Element, name: &str| e.attributes.get(name).cloned();
+        // The watchdog of classic chips with configuration change protection can only be
+        // changed through it.
+        let protected_watchdog = find_register(atdf, "CPU", "CCP").is_some();
+
+        // Enable bits and flags of each interrupt, with the vector it belongs to.
+        let mut found = Vec::new();
+        for interrupt in &interrupts {
+            let (Some(vector), Some(name)) = (
+                attribute_u32(interrupt, "index"),
+                attribute(interrupt, "name"),
+            ) else {
+                continue;
+            };
+            let (enable, pending) = match attribute(interrupt, "module-instance") {
+                Some(instance) => {
+                    let names = interrupts
+                        .iter()
+                        .filter(|i| attribute(i, "module-instance").as_ref() == Some(&instance))
+                        .filter_map(|i| attribute(i, "name"))
+                        .collect::<Vec<_>>();
+                    let names = names.iter().map(String::as_str).collect::<Vec<_>>();
+                    let enable = instance_enable(&registers, &instance, &name, &names);
+                    let pending = enable.and_then(|(r, mask)| instance_flags(&registers, r, mask));
+                    (enable, pending)
+                }
+                None => {
+                    let instance = name.split('_').next().unwrap_or_default();
+                    classic_enable_fields(&name)
+                        .into_iter()
+                        .find_map(|(field, index)| {
+                            let enable = find_bitfield(&registers, instance, &field, index)
+                                .filter(|(r, _)| {
+                                    !(protected_watchdog && r.name.starts_with("WDT"))
+                                })?;
+                            let flag = classic_flag_field(&field);
+                            Some((enable, find_bitfield(&registers, instance, &flag, index)))
+                        })
+                        .map_or((None, None), |(enable, pending)| (Some(enable), pending))
+                }
+            };
+            found.push((vector, enable, pending));
+        }
+
+        // Interrupt flags, which must be written as zero to not clear them, like `TWINT` in
+        // `TWCR` or `TXC0` in `UCSR0A`.
+        let mut flags = BTreeMap::<u32, u32>::new();
+        for register in &registers {
+            let captioned = register
+                .bitfields
+                .iter()
+                .filter(|b| b.2.to_lowercase().contains("flag"))
+                .fold(0, |flags, b| flags | b.1);
+            *flags.entry(register.address).or_default() |= captioned;
+        }
+        for (register, mask) in found.iter().filter_map(|(_, _, pending)| *pending) {
+            *flags.entry(register.address).or_default() |= mask;
+        }
+        let bits = |bits: Option<(&AtdfRegister, u32)>| {
+            let (register, mask) = bits?;
+            let flags = flags[&register.address];
+            match mask {
+                0x01..=0xff => Some((register.address, mask as u8, flags as u8)),
+                _ if mask & 0xff == 0 && mask <= 0xffff => {
+                    Some((register.address + 1, (mask >> 8) as u8, (flags >> 8) as u8))
+                }
+                _ => None,
+            }
+        };
+
+        // Bits of each vector, `None` if they are unknown or differ between the interrupts
+        // sharing the vector.
+        let mut enables = BTreeMap::<u32, Option<(u32, u8, u8)>>::new();
+        let mut pendings = BTreeMap::<u32, Option<(u32, u8, u8)>>::new();
+        for (vector, enable, pending) in found {
+            for (map, bits) in [(&mut enables, bits(enable)), (&mut pendings, bits(pending))] {
+                map.entry(vector)
+                    .and_modify(|known| {
+                        if *known != bits {
+                            *known = None;
+                        }
+                    })
+                    .or_insert(bits);
+            }
+        }
+
+        let arms = |map: &BTreeMap<u32, Option<(u32, u8, u8)>>, ty: &str| {
+            let mut arms = String::new();
+            let mut known = 0;
+            for (vector, variant) in &variants {
+                if let Some(Some((register, mask, flags))) = map.get(vector) {
+                    known += 1;
+                    arms.push_str(&format!(
+                        "
+            {}::{} => Some(crate::interrupt::{} {{
+                register: 0x{:04x},
+                mask: 0x{:02x},
+                flags: 0x{:02x},
+            }}),",
+                        enum_path, variant, ty, register, mask, flags
+                    ));
+                }
+            }
+            if known < variants.len() {
+                arms.push_str("\n            _ => None,");
+            }
+            arms
+        };
+
+        let mut code = format!(
+            r#"
+impl {} {{
+    /// Returns the bits which enable this interrupt, if they are known.
+    #[inline]
+    pub const fn enable_bits(self) -> Option<crate::interrupt::EnableBits> {{
+        match self {{{}
+        }}
+    }}
+
+    /// Returns the flag which signals that this interrupt is pending, if it is known.
+    #[inline]
+    pub const fn pending_bits(self) -> Option<crate::interrupt::PendingBits> {{
+        match self {{{}
+        }}
+    }}
+
+    /// Returns whether this interrupt is enabled.
+    ///
+    /// Panics if its enable bits aren't known.
+    #[inline]
+    pub fn is_enabled(self) -> bool {{
+        // SAFETY: The bits belong to this chip.
+        unsafe {{ self.enable_bits().expect(UNKNOWN_ENABLE_BITS).is_enabled() }}
+    }}
+
+    /// Enables this interrupt, by setting all of its enable bits.
+    ///
+    /// Panics if its enable bits aren't known.
+    ///
+    /// # Safety
+    ///
+    /// - Do not call this function inside a [crate::interrupt::mask] of this interrupt.
+    #[inline]
+    pub unsafe fn enable(self) {{
+        self.enable_bits().expect(UNKNOWN_ENABLE_BITS).enable()
+    }}
+
+    /// Disables this interrupt, by clearing all of its enable bits.
+    ///
+    /// Panics if its enable bits aren't known.
+    #[inline]
+    pub fn disable(self) {{
+        // SAFETY: The bits belong to this chip.
+        unsafe {{ self.enable_bits().expect(UNKNOWN_ENABLE_BITS).disable() }}
+    }}
+
+    /// Returns whether this interrupt is pending, i.e. its flag is set.
+    ///
+    /// Panics if its flag isn't known.
+    #[inline]
+    pub fn is_pending(self) -> bool {{
+        // SAFETY: The flag belongs to this chip.
+        unsafe {{ self.pending_bits().expect(UNKNOWN_FLAG).is_pending() }}
+    }}
+
+    /// Clears the flag of this interrupt, see [crate::interrupt::PendingBits::clear].
+    ///
+    /// Panics if its flag isn't known.
+    #[inline]
+    pub fn clear_pending(self) {{
+        // SAFETY: The flag belongs to this chip.
+        unsafe {{ self.pending_bits().expect(UNKNOWN_FLAG).clear() }}
+    }}
+}}
+
+const UNKNOWN_ENABLE_BITS: &str = "The enable bits of this interrupt are unknown";
+const UNKNOWN_FLAG: &str = "The flag of this interrupt is unknown";
+"#,
+            enum_path,
+            arms(&enables, "EnableBits"),
+            arms(&pendings, "PendingBits"),
+        );
+        if enum_path != "Interrupt" {
+            // Chip-independent APIs refer to the enum as `Interrupt`.
+            code.push_str(&format!("\npub use self::{};\n", enum_path));
+        }
+        append_to_module(gen_dirs, mcu, &code)?;
+    }
+    Ok(())
+}
+
+fn generate_cpuint(
+    gen_dirs: &GenerationDirs,
+    atdfs: &BTreeMap<String, xmltree::Element>,
+) -> Result<(), ()> {
+    for (mcu, atdf) in atdfs {
+        // Only newer chips have a CPU interrupt controller.
+        let controller = match (
+            find_register(atdf, "CPUINT", "CTRLA"),
+            find_register(atdf, "CPUINT", "LVL1VEC"),
+        ) {
+            (Some((ctrla, bitfields)), Some((lvl1vec, _))) => {
+                match (bitfields.get("LVL0RR"), bitfields.get("CVT")) {
+                    (Some(round_robin), Some(compact_vector_table)) => format!(
+                        "Some(crate::cpuint::InterruptController {{
+    ctrla: 0x{:04x},
+    lvl1vec: 0x{:04x},
+    round_robin: 0x{:02x},
+    compact_vector_table: 0x{:02x},
+}})",
+                        ctrla, lvl1vec, round_robin, compact_vector_table,
+                    ),
+                    _ => {
+                        println!(
+                            "cargo::error=Failed to find the CPUINT.CTRLA bits of {}!",
+                            mcu
+                        );
+                        return Err(());
+                    }
+                }
+            }
+            _ => "None".to_owned(),
+        };
+
+        let code = format!(
+            r#"
+/// Description of the CPU interrupt controller (`CPUINT`), if this chip has one, see
+/// [`crate::cpuint`].
+pub const CPUINT: Option<crate::cpuint::InterruptController> = {};
+"#,
+            controller,
+        );
+        append_to_module(gen_dirs, mcu, &code)?;
+    }
+    Ok(())
+}
+
+fn generate_selected_module(
+    gen_dirs: &GenerationDirs,
+    mcus: &BTreeMap<String, McuInputs>,
+) -> Result<(), ()> {
+    // Chip-independent APIs need to know which chip they are running on, so they are only
+    // available if exactly one MCU is selected.
+    println!("cargo::rustc-check-cfg=cfg(avr_device_single_mcu)");
+    let code = match mcus.keys().collect::<Vec<_>>().as_slice() {
+        [mcu] => {
+            println!("cargo::rustc-cfg=avr_device_single_mcu");
+            format!(
+                "/// Module of the selected MCU, used by chip-independent APIs.\n\
+                pub(crate) use crate::devices::{} as selected;\n",
+                mcu
+            )
+        }
+        _ => String::new(),
+    };
+    match fs::write(gen_dirs.module.join("selected.rs"), code) {
+        Ok(()) => Ok(()),
+        Err(e) => {
+            println!("cargo::error=Failed to write selected MCU file!\n{}", e);
+            Err(())
+        }
+    }
+}
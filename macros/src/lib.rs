@@ -123,37 +123,130 @@ pub fn entry(
 }
 
 #[proc_macro_attribute]
-pub fn interrupt(
+pub fn pre_init(
     args: proc_macro::TokenStream,
     input: proc_macro::TokenStream,
 ) -> proc_macro::TokenStream {
-    let mut f: syn::ItemFn =
-        syn::parse(input).expect("`#[interrupt]` must be applied to a function");
-    let args: Vec<_> = args.into_iter().collect();
+    let mut f = syn::parse_macro_input!(input as syn::ItemFn);
 
-    let fspan = f.span();
-    let ident = f.sig.ident.clone();
+    // check the function signature
+    let valid_signature = f.sig.constness.is_none()
+        && f.vis == syn::Visibility::Inherited
+        && f.sig.unsafety.is_some()
+        && f.sig.abi.is_none()
+        && f.sig.inputs.is_empty()
+        && f.sig.generics.params.is_empty()
+        && f.sig.generics.where_clause.is_none()
+        && f.sig.variadic.is_none()
+        && match f.sig.output {
+            syn::ReturnType::Default => true,
+            syn::ReturnType::Type(_, ref ty) => match **ty {
+                syn::Type::Tuple(ref tuple) => tuple.elems.is_empty(),
+                _ => false,
+            },
+        };
 
-    let chip = if let Some(tree) = args.get(0) {
-        if let proc_macro::TokenTree::Ident(ident) = tree {
-            syn::Ident::new(&ident.to_string(), fspan)
-        } else {
-            return syn::parse::Error::new(
-                proc_macro2::Span::call_site(),
-                "#[interrupt(chip)]: chip must be an ident",
-            )
-            .to_compile_error()
-            .into();
-        }
-    } else {
+    if !valid_signature {
+        return syn::parse::Error::new(
+            f.span(),
+            "`#[pre_init]` function must have signature `unsafe fn()`",
+        )
+        .to_compile_error()
+        .into();
+    }
+
+    if !args.is_empty() {
         return syn::parse::Error::new(
             proc_macro2::Span::call_site(),
-            "#[interrupt(chip)] needs a chip argument",
+            "This attribute accepts no arguments",
+        )
+        .to_compile_error()
+        .into();
+    }
+
+    // `static mut` resources live in .data/.bss which are only initialized after this function
+    // has run, so they cannot be handed out here.
+    if let Some(statik) = f.block.stmts.iter().find_map(|stmt| match stmt {
+        syn::Stmt::Item(syn::Item::Static(statik)) if statik.mutability.is_some() => Some(statik),
+        _ => None,
+    }) {
+        return syn::parse::Error::new(
+            statik.span(),
+            "`static mut` resources are not available in a `#[pre_init]` function",
         )
         .to_compile_error()
         .into();
+    }
+
+    if let Err(error) = check_attr_whitelist(&f.attrs, WhiteListCaller::PreInit) {
+        return error;
+    }
+
+    // Rename the function so it is not callable
+    f.sig.ident = syn::Ident::new(
+        &format!("__avr_device_rt_{}", f.sig.ident),
+        proc_macro2::Span::call_site(),
+    );
+
+    let tramp_ident = syn::Ident::new(
+        &format!("{}_trampoline", f.sig.ident),
+        proc_macro2::Span::call_site(),
+    );
+    let ident = &f.sig.ident;
+
+    let (ref cfgs, ref attrs) = extract_cfgs(f.attrs.clone());
+
+    // .init3 runs after the stack pointer and the zero register were set up (.init2) but before
+    // .data and .bss are initialized (.init4).  The code in .initN sections falls through from one
+    // section to the next, so the function is called from there.  `icall` together with `gs()`
+    // reaches the whole flash on all devices, even those without `call`.
+    quote::quote! (
+        #[cfg(not(any(doc, target_arch = "avr")))]
+        compile_error!(
+            "Ensure that you are using an AVR target! You may need to change \
+       directories or pass a --target flag to cargo. See
+       https://github.com/Rahix/avr-device/pull/41 for more details."
+        );
+
+        #(#cfgs)*
+        #(#attrs)*
+        #[doc(hidden)]
+        #[export_name = "__avr_device_pre_init"]
+        pub unsafe extern "C" fn #tramp_ident() {
+            #ident()
+        }
+
+        #(#cfgs)*
+        #[cfg(target_arch = "avr")]
+        ::core::arch::global_asm!(
+            ".pushsection .init3,\"ax\",@progbits",
+            "ldi r30, lo8(gs(__avr_device_pre_init))",
+            "ldi r31, hi8(gs(__avr_device_pre_init))",
+            "icall",
+            ".popsection",
+        );
+
+        #[doc(hidden)]
+        #f
+    )
+    .into()
+}
+
+#[proc_macro_attribute]
+pub fn interrupt(
+    args: proc_macro::TokenStream,
+    input: proc_macro::TokenStream,
+) -> proc_macro::TokenStream {
+    let mut f: syn::ItemFn =
+        syn::parse(input).expect("`#[interrupt]` must be applied to a function");
+    let args = match parse_interrupt_args(args) {
+        Ok(args) => args,
+        Err(e) => return e.to_compile_error().into(),
     };
 
+    let fspan = f.span();
+    let ident = f.sig.ident.clone();
+
     let valid_signature = f.sig.constness.is_none()
         && f.vis == syn::Visibility::Inherited
         && f.sig.abi.is_none()
@@ -179,11 +272,77 @@ pub fn interrupt(
         .into();
     }
 
+    let chip = args.chip.as_ref().map(|chip| quote::quote!(@#chip,));
+
+    // An empty handler only consists of a `reti` instruction.  There is no function to call, so
+    // each listed vector gets its own `reti`.
+    if args.empty {
+        if args.nonblocking {
+            return syn::parse::Error::new(
+                proc_macro2::Span::call_site(),
+                "`empty` and `nonblocking` cannot be combined",
+            )
+            .to_compile_error()
+            .into();
+        }
+        if !f.block.stmts.is_empty() {
+            return syn::parse::Error::new(
+                f.block.span(),
+                "`empty` interrupt handlers must have an empty body",
+            )
+            .to_compile_error()
+            .into();
+        }
+        if let Err(error) = check_attr_whitelist(&f.attrs, WhiteListCaller::Interrupt) {
+            return error;
+        }
+
+        let (ref cfgs, _) = extract_cfgs(f.attrs.clone());
+        let vectors = if args.vectors.is_empty() {
+            vec![ident]
+        } else {
+            args.vectors
+        };
+        let vectors = vectors.iter().map(|vector| {
+            quote::quote! {
+                #(#cfgs)*
+                ::avr_device::__avr_device_asm_vector!(#chip #vector, reti);
+            }
+        });
+        return quote::quote!(#(#vectors)*).into();
+    }
+
     let (statics, stmts) = match extract_static_muts(f.block.stmts.iter().cloned()) {
         Err(e) => return e.to_compile_error().into(),
         Ok(x) => x,
     };
 
+    // A non-blocking handler runs with interrupts enabled and can thus be preempted by another
+    // invocation of itself.  Handing out `&mut` references to resources would not be sound then.
+    if args.nonblocking {
+        if let Some(statik) = statics.first() {
+            return syn::parse::Error::new(
+                statik.span(),
+                "`static mut` resources are not available in a `nonblocking` interrupt handler",
+            )
+            .to_compile_error()
+            .into();
+        }
+    }
+
+    // A level 1 handler preempts level 0 handlers (on chips with a CPUINT controller).  If it
+    // serves multiple vectors, its level 1 invocation could preempt a level 0 invocation of itself.
+    if args.level1 && args.vectors.len() > 1 {
+        if let Some(statik) = statics.first() {
+            return syn::parse::Error::new(
+                statik.span(),
+                "`static mut` resources are not available in a `level1` interrupt handler serving multiple vectors",
+            )
+            .to_compile_error()
+            .into();
+        }
+    }
+
     f.sig.ident = syn::Ident::new(
         &format!("__avr_device_rt_{}", f.sig.ident),
         proc_macro2::Span::call_site(),
@@ -229,21 +388,210 @@ pub fn interrupt(
     );
     let interrupt_ident = &f.sig.ident;
 
+    // Without a list of vectors, the function name is the name of the interrupt.  Otherwise, the
+    // trampoline is exported for the first listed vector and all others jump to it.
+    let (vector, aliases) = match args.vectors.split_first() {
+        Some((vector, aliases)) => (vector, aliases),
+        None => (&ident, &[][..]),
+    };
+    let aliases = aliases
+        .iter()
+        .map(|alias| {
+            quote::quote! {
+                #(#cfgs)*
+                ::avr_device::__avr_device_asm_vector!(#chip #alias, jump #tramp_ident);
+            }
+        })
+        .collect::<Vec<_>>();
+    let abi = if args.nonblocking {
+        "avr-non-blocking-interrupt"
+    } else {
+        "avr-interrupt"
+    };
+
     quote::quote! {
         #(#cfgs)*
         #(#attrs)*
-        ::avr_device::__avr_device_trampoline!(@#chip, #ident, pub extern "avr-interrupt" fn #tramp_ident() {
+        ::avr_device::__avr_device_trampoline!(#chip #vector, pub extern #abi fn #tramp_ident() {
             #[allow(static_mut_refs)]
             #interrupt_ident(
                 #(#resource_args),*
             )
         });
 
+        #(#aliases)*
+
+        #f
+    }
+    .into()
+}
+
+#[proc_macro_attribute]
+pub fn default_handler(
+    args: proc_macro::TokenStream,
+    input: proc_macro::TokenStream,
+) -> proc_macro::TokenStream {
+    let mut f = syn::parse_macro_input!(input as syn::ItemFn);
+
+    let valid_signature = f.sig.constness.is_none()
+        && f.vis == syn::Visibility::Inherited
+        && f.sig.abi.is_none()
+        && f.sig.inputs.is_empty()
+        && f.sig.generics.params.is_empty()
+        && f.sig.generics.where_clause.is_none()
+        && f.sig.variadic.is_none()
+        && match f.sig.output {
+            syn::ReturnType::Default => true,
+            syn::ReturnType::Type(_, ref ty) => match **ty {
+                syn::Type::Tuple(ref tuple) => tuple.elems.is_empty(),
+                syn::Type::Never(..) => true,
+                _ => false,
+            },
+        };
+
+    if !valid_signature {
+        return syn::parse::Error::new(
+            f.span(),
+            "`#[default_handler]` function must have signature `[unsafe] fn() [-> !]`",
+        )
+        .to_compile_error()
+        .into();
+    }
+
+    if !args.is_empty() {
+        return syn::parse::Error::new(
+            proc_macro2::Span::call_site(),
+            "This attribute accepts no arguments",
+        )
+        .to_compile_error()
+        .into();
+    }
+
+    let (statics, stmts) = match extract_static_muts(f.block.stmts) {
+        Err(e) => return e.to_compile_error().into(),
+        Ok(x) => x,
+    };
+
+    f.sig.ident = syn::Ident::new(
+        &format!("__avr_device_rt_{}", f.sig.ident),
+        proc_macro2::Span::call_site(),
+    );
+    f.sig.inputs.extend(statics.iter().map(|statik| {
+        let ident = &statik.ident;
+        let ty = &statik.ty;
+        let attrs = &statik.attrs;
+        syn::parse::<syn::FnArg>(
+            quote::quote!(#[allow(non_snake_case)] #(#attrs)* #ident: &mut #ty).into(),
+        )
+        .unwrap()
+    }));
+    f.block.stmts = stmts;
+
+    let resource_args = statics
+        .iter()
+        .map(|statik| {
+            let (ref cfgs, ref attrs) = extract_cfgs(statik.attrs.clone());
+            let ident = &statik.ident;
+            let ty = &statik.ty;
+            let expr = &statik.expr;
+            quote::quote! {
+                #(#cfgs)*
+                unsafe {
+                    #(#attrs)*
+                    static mut #ident: #ty = #expr;
+                    &mut #ident
+                }
+            }
+        })
+        .collect::<Vec<_>>();
+
+    if let Err(error) = check_attr_whitelist(&f.attrs, WhiteListCaller::DefaultHandler) {
+        return error;
+    }
+
+    let (ref cfgs, ref attrs) = extract_cfgs(f.attrs.clone());
+
+    let tramp_ident = syn::Ident::new(
+        &format!("{}_trampoline", f.sig.ident),
+        proc_macro2::Span::call_site(),
+    );
+    let ident = &f.sig.ident;
+
+    // avr-libc's startup code routes all vectors without a handler to `__bad_interrupt`, which
+    // jumps to `__vector_default`.  Only if that symbol is not defined, the reset vector is used.
+    quote::quote! {
+        #(#cfgs)*
+        #(#attrs)*
+        #[doc(hidden)]
+        #[export_name = "__vector_default"]
+        pub unsafe extern "avr-interrupt" fn #tramp_ident() {
+            #[allow(static_mut_refs)]
+            #ident(
+                #(#resource_args),*
+            )
+        }
+
+        #[doc(hidden)]
         #f
     }
     .into()
 }
 
+struct InterruptArgs {
+    chip: Option<syn::Ident>,
+    vectors: Vec<syn::Ident>,
+    nonblocking: bool,
+    empty: bool,
+    level1: bool,
+}
+
+/// Parses the arguments of `#[interrupt(...)]`
+///
+/// All arguments are optional: `[chip][, VECTOR...][, nonblocking | empty][, level1]`.
+fn parse_interrupt_args(args: proc_macro::TokenStream) -> Result<InterruptArgs, syn::parse::Error> {
+    use syn::parse::Parser;
+
+    let args =
+        syn::punctuated::Punctuated::<syn::Ident, syn::Token![,]>::parse_terminated.parse(args)?;
+
+    let mut parsed = InterruptArgs {
+        chip: None,
+        vectors: Vec::new(),
+        nonblocking: false,
+        empty: false,
+        level1: false,
+    };
+    for (i, arg) in args.into_iter().enumerate() {
+        let name = arg.to_string();
+        if name == "nonblocking" {
+            parsed.nonblocking = true;
+        } else if name == "empty" {
+            parsed.empty = true;
+        } else if name == "level1" {
+            parsed.level1 = true;
+        } else if i == 0 && name.starts_with(|c: char| c.is_ascii_lowercase()) {
+            // Chip names are lowercase and must come first.  Without a chip, the vector is looked
+            // up in the table of the only MCU that is currently selected (see
+            // `__avr_device_trampoline!`).
+            parsed.chip = Some(arg);
+        } else if name.starts_with(|c: char| c.is_ascii_uppercase()) {
+            if parsed.vectors.contains(&arg) {
+                return Err(syn::parse::Error::new(
+                    arg.span(),
+                    format!("interrupt `{}` is listed multiple times", arg),
+                ));
+            }
+            parsed.vectors.push(arg);
+        } else {
+            return Err(syn::parse::Error::new(
+                arg.span(),
+                format!("unknown argument `{}` to #[interrupt]", arg),
+            ));
+        }
+    }
+    Ok(parsed)
+}
+
 /// Extracts `static mut` vars from the beginning of the given statements
 fn extract_static_muts(
     stmts: impl IntoIterator<Item = syn::Stmt>,
@@ -253,7 +601,7 @@ fn extract_static_muts(
     let mut seen = std::collections::HashSet::new();
     let mut statics = vec![];
     let mut stmts = vec![];
-    while let Some(stmt) = istmts.next() {
+    for stmt in istmts.by_ref() {
         match stmt {
             syn::Stmt::Item(syn::Item::Static(var)) => {
                 if var.mutability.is_some() {
@@ -300,6 +648,8 @@ fn extract_cfgs(attrs: Vec<syn::Attribute>) -> (Vec<syn::Attribute>, Vec<syn::At
 enum WhiteListCaller {
     Entry,
     Interrupt,
+    DefaultHandler,
+    PreInit,
 }
 
 fn check_attr_whitelist(attrs: &[syn::Attribute], caller: WhiteListCaller) -> Result<(), proc_macro::TokenStream> {
@@ -327,6 +677,12 @@ fn check_attr_whitelist(attrs: &[syn::Attribute], caller: WhiteListCaller) -> Re
             WhiteListCaller::Interrupt => {
                 "this attribute is not allowed on an interrupt handler controlled by avr-device"
             }
+            WhiteListCaller::DefaultHandler => {
+                "this attribute is not allowed on an avr-device default interrupt handler"
+            }
+            WhiteListCaller::PreInit => {
+                "this attribute is not allowed on an avr-device pre-init function"
+            }
         };
 
         return Err(syn::parse::Error::new(attr.span(), err_str)
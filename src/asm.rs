@@ -9,6 +9,8 @@ pub fn nop() {
     cfg_if::cfg_if! {
         if #[cfg(target_arch = "avr")] {
             unsafe { asm!("nop") }
+        } else if #[cfg(feature = "host-mock")] {
+            crate::mock::record(crate::mock::Event::Nop)
         } else {
             unimplemented!()
         }
@@ -21,6 +23,8 @@ pub fn sleep() {
     cfg_if::cfg_if! {
         if #[cfg(target_arch = "avr")] {
             unsafe { asm!("sleep") }
+        } else if #[cfg(feature = "host-mock")] {
+            crate::mock::record(crate::mock::Event::Sleep)
         } else {
             unimplemented!()
         }
@@ -33,6 +37,8 @@ pub fn wdr() {
     cfg_if::cfg_if! {
         if #[cfg(target_arch = "avr")] {
             unsafe { asm!("wdr") }
+        } else if #[cfg(feature = "host-mock")] {
+            crate::mock::record(crate::mock::Event::WatchdogReset)
         } else {
             unimplemented!()
         }
@@ -45,12 +51,13 @@ pub fn wdr() {
 /// has some caveats:
 ///
 /// - The delay may be significantly longer if an interrupt is serviced at the
-/// same time, since the delay loop will not be executing during the interrupt.
-/// If you need precise timing, use a hardware timer peripheral instead.
+///   same time, since the delay loop will not be executing during the interrupt.
+///   If you need precise timing, use a hardware timer peripheral instead.
 ///
 /// - The real-time delay depends on the CPU clock frequency. If you want to
-/// conveniently specify a delay value in real-time units like microseconds,
-/// then use the `delay` module in the HAL crate for your platform.
+///   conveniently specify a delay value in real-time units like microseconds,
+///   then use [`delay_us`] and friends or the `delay` module in the HAL crate
+///   for your platform.
 #[inline(always)]
 pub fn delay_cycles(cycles: u32) {
     cfg_if::cfg_if! {
@@ -76,9 +83,289 @@ pub fn delay_cycles(cycles: u32) {
                     r3 = inout(reg_upper) cycles_bytes[3],
                 )
             }
+        } else if #[cfg(feature = "host-mock")] {
+            crate::mock::advance(cycles)
         } else {
             let _ = cycles;
             unimplemented!()
         }
     }
 }
+
+/// Blocks the program for at least `NS` nanoseconds, at a CPU clock frequency
+/// of `F_CPU` Hz.
+///
+/// The delay is rounded up to whole CPU cycles, which are spent exactly,
+/// without the loop granularity of [`delay_cycles`]. Both parameters are
+/// const generics, so the cycle count is computed at compile time:
+///
+/// ```ignore
+/// const F_CPU: u32 = 16_000_000;
+///
+/// avr_device::asm::delay_ns::<F_CPU, 250>();
+/// ```
+///
+/// As with [`delay_cycles`], the delay is longer if an interrupt is serviced
+/// at the same time. Delays of more than `u32::MAX` cycles fail to compile.
+#[inline(always)]
+pub fn delay_ns<const F_CPU: u32, const NS: u32>() {
+    Delay::<F_CPU, NS, 1_000_000_000>::delay()
+}
+
+/// Blocks the program for at least `US` microseconds, at a CPU clock frequency
+/// of `F_CPU` Hz.
+///
+/// See [`delay_ns`] for details.
+#[inline(always)]
+pub fn delay_us<const F_CPU: u32, const US: u32>() {
+    Delay::<F_CPU, US, 1_000_000>::delay()
+}
+
+/// Blocks the program for at least `MS` milliseconds, at a CPU clock frequency
+/// of `F_CPU` Hz.
+///
+/// See [`delay_ns`] for details.
+#[inline(always)]
+pub fn delay_ms<const F_CPU: u32, const MS: u32>() {
+    Delay::<F_CPU, MS, 1_000>::delay()
+}
+
+/// Delay of `AMOUNT` times `1 / PER_SECOND` seconds at `F_CPU` Hz.
+///
+/// The delay is a countdown loop with a counter of one to four bytes, followed
+/// by padding for the cycles which are left over. With a counter of `BYTES`
+/// bytes, loading the counter takes `BYTES` cycles and every iteration takes
+/// `BYTES + 2` cycles, except for the last one, which takes one cycle less.
+struct Delay<const F_CPU: u32, const AMOUNT: u32, const PER_SECOND: u32>;
+
+impl<const F_CPU: u32, const AMOUNT: u32, const PER_SECOND: u32> Delay<F_CPU, AMOUNT, PER_SECOND> {
+    /// Length of the delay in CPU cycles, rounded up.
+    const CYCLES: u32 = {
+        let cycles = (F_CPU as u64 * AMOUNT as u64).div_ceil(PER_SECOND as u64);
+        assert!(
+            cycles <= u32::MAX as u64,
+            "Delay is too long for this CPU frequency"
+        );
+        cycles as u32
+    };
+
+    /// Width of the loop counter in bytes, zero for delays too short for a loop.
+    /// Each width is used up to the longest delay it can produce.
+    const BYTES: u32 = match Self::CYCLES {
+        0..=2 => 0,
+        3..=770 => 1,
+        771..=262_148 => 2,
+        262_149..=83_886_086 => 3,
+        _ => 4,
+    };
+
+    /// Number of loop iterations.  A counter of zero makes for the maximum
+    /// number of iterations, i.e. `1 << (8 * BYTES)`.
+    const ITERATIONS: u32 = match Self::BYTES {
+        0 => 0,
+        4 => (Self::CYCLES - 3) / 6,
+        bytes => {
+            let iterations = (Self::CYCLES - bytes + 1) / (bytes + 2);
+            let max = 1 << (8 * bytes);
+            if iterations < max {
+                iterations
+            } else {
+                max
+            }
+        }
+    };
+
+    /// Cycles left over after the loop, spent with `rjmp .+0` and `nop`.
+    const PADDING: u32 = match Self::BYTES {
+        0 => Self::CYCLES,
+        bytes => Self::CYCLES - (bytes - 1) - (bytes + 2) * Self::ITERATIONS,
+    };
+
+    #[inline(always)]
+    fn delay() {
+        cfg_if::cfg_if! {
+            if #[cfg(target_arch = "avr")] {
+                macro_rules! delay_asm {
+                    ($($template:literal,)* ; $($operands:tt)*) => {
+                        asm!(
+                            $($template,)*
+                            ".rept {pairs}",
+                            "rjmp .+0",
+                            ".endr",
+                            ".rept {single}",
+                            "nop",
+                            ".endr",
+                            pairs = const Self::PADDING / 2,
+                            single = const Self::PADDING % 2,
+                            $($operands)*
+                            options(nomem, nostack),
+                        )
+                    };
+                }
+
+                // The counter is loaded inside the assembly, so the compiler can't
+                // add any cycles.  All but one of these branches are removed at
+                // compile time.
+                unsafe {
+                    match Self::BYTES {
+                        0 => delay_asm!(;),
+                        1 => delay_asm!(
+                            "ldi {r0}, {n0}",
+                            "1:",
+                            "subi {r0}, 1",
+                            "brne 1b",
+                            ;
+                            n0 = const Self::ITERATIONS & 0xff,
+                            r0 = out(reg_upper) _,
+                        ),
+                        2 => delay_asm!(
+                            "ldi {r0}, {n0}",
+                            "ldi {r1}, {n1}",
+                            "1:",
+                            "subi {r0}, 1",
+                            "sbci {r1}, 0",
+                            "brne 1b",
+                            ;
+                            n0 = const Self::ITERATIONS & 0xff,
+                            n1 = const (Self::ITERATIONS >> 8) & 0xff,
+                            r0 = out(reg_upper) _,
+                            r1 = out(reg_upper) _,
+                        ),
+                        3 => delay_asm!(
+                            "ldi {r0}, {n0}",
+                            "ldi {r1}, {n1}",
+                            "ldi {r2}, {n2}",
+                            "1:",
+                            "subi {r0}, 1",
+                            "sbci {r1}, 0",
+                            "sbci {r2}, 0",
+                            "brne 1b",
+                            ;
+                            n0 = const Self::ITERATIONS & 0xff,
+                            n1 = const (Self::ITERATIONS >> 8) & 0xff,
+                            n2 = const (Self::ITERATIONS >> 16) & 0xff,
+                            r0 = out(reg_upper) _,
+                            r1 = out(reg_upper) _,
+                            r2 = out(reg_upper) _,
+                        ),
+                        _ => delay_asm!(
+                            "ldi {r0}, {n0}",
+                            "ldi {r1}, {n1}",
+                            "ldi {r2}, {n2}",
+                            "ldi {r3}, {n3}",
+                            "1:",
+                            "subi {r0}, 1",
+                            "sbci {r1}, 0",
+                            "sbci {r2}, 0",
+                            "sbci {r3}, 0",
+                            "brne 1b",
+                            ;
+                            n0 = const Self::ITERATIONS & 0xff,
+                            n1 = const (Self::ITERATIONS >> 8) & 0xff,
+                            n2 = const (Self::ITERATIONS >> 16) & 0xff,
+                            n3 = const Self::ITERATIONS >> 24,
+                            r0 = out(reg_upper) _,
+                            r1 = out(reg_upper) _,
+                            r2 = out(reg_upper) _,
+                            r3 = out(reg_upper) _,
+                        ),
+                    }
+                }
+            } else if #[cfg(feature = "host-mock")] {
+                crate::mock::advance(Self::CYCLES)
+            } else {
+                let _ = Self::CYCLES;
+                unimplemented!()
+            }
+        }
+    }
+}
+
+// Compile-time checks of the delay loops at common CPU clock frequencies.  Besides the layout,
+// the cycles are counted again from the instructions which `Delay::delay` emits, using the
+// counter values which are loaded into the registers.
+const _: () = {
+    // Cycles of `ldi` for each counter byte, `subi` and `sbci` for each counter byte per
+    // iteration, `brne` taken in all but the last iteration, and `rjmp .+0` and `nop` for padding.
+    const fn emitted_cycles(bytes: u32, iterations: u32, padding: u32) -> u64 {
+        let loop_cycles = match bytes {
+            0 => 0,
+            bytes => {
+                let counter = iterations as u64 & ((1 << (8 * bytes)) - 1);
+                let iterations = if counter == 0 {
+                    1 << (8 * bytes)
+                } else {
+                    counter
+                };
+                bytes as u64 + iterations * bytes as u64 + 2 * (iterations - 1) + 1
+            }
+        };
+        loop_cycles + 2 * (padding / 2) as u64 + (padding % 2) as u64
+    }
+
+    macro_rules! check {
+        ($($f_cpu:literal, $amount:literal / $per_second:literal => $layout:expr;)*) => {
+            $(
+                let (cycles, bytes, iterations, padding) = $layout;
+                assert!(Delay::<$f_cpu, $amount, $per_second>::CYCLES == cycles);
+                assert!(Delay::<$f_cpu, $amount, $per_second>::BYTES == bytes);
+                assert!(Delay::<$f_cpu, $amount, $per_second>::ITERATIONS == iterations);
+                assert!(Delay::<$f_cpu, $amount, $per_second>::PADDING == padding);
+                assert!(emitted_cycles(bytes, iterations, padding) == cycles as u64);
+            )*
+        };
+    }
+
+    // (cycles, counter bytes, iterations, padding cycles)
+    check! {
+        1_000_000, 250 / 1_000_000_000 => (1, 0, 0, 1);
+        1_000_000, 1 / 1_000_000 => (1, 0, 0, 1);
+        1_000_000, 10 / 1_000_000 => (10, 1, 3, 1);
+        1_000_000, 1 / 1_000 => (1_000, 2, 249, 3);
+        8_000_000, 125 / 1_000_000_000 => (1, 0, 0, 1);
+        8_000_000, 1 / 1_000_000 => (8, 1, 2, 2);
+        8_000_000, 1 / 1_000 => (8_000, 2, 1_999, 3);
+        8_000_000, 100 / 1_000 => (800_000, 3, 159_999, 3);
+        16_000_000, 62 / 1_000_000_000 => (1, 0, 0, 1);
+        16_000_000, 1 / 1_000_000 => (16, 1, 5, 1);
+        16_000_000, 48 / 1_000_000 => (768, 1, 256, 0);
+        16_000_000, 1 / 1_000 => (16_000, 2, 3_999, 3);
+        16_000_000, 1_000 / 1_000 => (16_000_000, 3, 3_199_999, 3);
+        20_000_000, 125 / 1_000_000_000 => (3, 1, 1, 0);
+        20_000_000, 1 / 1_000_000 => (20, 1, 6, 2);
+        20_000_000, 1 / 1_000 => (20_000, 2, 4_999, 3);
+        20_000_000, 5_000 / 1_000 => (100_000_000, 4, 16_666_666, 1);
+    }
+
+    // The longest delay of each counter width runs the maximum number of iterations, loaded as a
+    // counter of zero, and the next delay switches to a wider counter.
+    check! {
+        1_000_000, 770 / 1_000_000 => (770, 1, 256, 2);
+        1_000_000, 771 / 1_000_000 => (771, 2, 192, 2);
+        1_000_000, 262_148 / 1_000_000 => (262_148, 2, 65_536, 3);
+        1_000_000, 262_149 / 1_000_000 => (262_149, 3, 52_429, 2);
+        1_000_000, 83_886_086 / 1_000_000 => (83_886_086, 3, 16_777_216, 4);
+        1_000_000, 83_886_087 / 1_000_000 => (83_886_087, 4, 13_981_014, 0);
+        1, 4_294_967_295 / 1 => (4_294_967_295, 4, 715_827_882, 0);
+    }
+};
+
+#[cfg(all(test, feature = "host-mock"))]
+mod tests {
+    use crate::mock;
+
+    #[test]
+    fn delays_advance_the_cycle_counter() {
+        let start = mock::cycles();
+        super::delay_us::<16_000_000, 1>();
+        assert_eq!(mock::cycles() - start, 16);
+        super::delay_ms::<8_000_000, 1>();
+        assert_eq!(mock::cycles() - start, 16 + 8_000);
+        super::delay_us::<16_000_000, 48>();
+        assert_eq!(mock::cycles() - start, 16 + 8_000 + 768);
+        super::delay_ns::<20_000_000, 125>();
+        assert_eq!(mock::cycles() - start, 16 + 8_000 + 768 + 3);
+        super::delay_cycles(1_000);
+        assert_eq!(mock::cycles() - start, 16 + 8_000 + 768 + 3 + 1_000);
+    }
+}
@@ -0,0 +1,353 @@
+//! Atomic Integers
+//!
+//! AVR can only load and store single bytes atomically, so `core::sync::atomic` has no atomic
+//! integers wider than 8 bits, and no read-modify-write operations.  The types of this module
+//! offer the same API for 16- and 32-bit integers, and disable interrupts for the few cycles of
+//! each operation, so values shared with interrupt handlers don't tear:
+//!
+//! ```ignore
+//! use avr_device::atomic::AtomicU32;
+//! use core::sync::atomic::Ordering;
+//!
+//! static TICKS: AtomicU32 = AtomicU32::new(0);
+//!
+//! #[avr_device::interrupt(atmega328p)]
+//! fn TIMER0_COMPA() {
+//!     TICKS.fetch_add(1, Ordering::Relaxed);
+//! }
+//!
+//! fn millis() -> u32 {
+//!     TICKS.load(Ordering::Relaxed)
+//! }
+//! ```
+//!
+//! Each operation is a [critical section](crate::interrupt::free) and thus sequentially
+//! consistent.  The `Ordering` arguments are only accepted for compatibility with
+//! `core::sync::atomic` and are ignored.
+//!
+//! On other architectures, e.g. in tests on the host, the types of `core::sync::atomic` store the
+//! values instead, with sequentially consistent operations as well.
+
+#[cfg(target_arch = "avr")]
+use core::cell::UnsafeCell;
+use core::sync::atomic::Ordering;
+
+macro_rules! atomic_int {
+    ($name:ident, $int:ty) => {
+        #[doc = concat!("An [`", stringify!($int), "`] which can be shared with interrupt handlers.")]
+        ///
+        /// This has the same in-memory representation as the underlying integer type.
+        #[repr(transparent)]
+        pub struct $name {
+            #[cfg(target_arch = "avr")]
+            value: UnsafeCell<$int>,
+            #[cfg(not(target_arch = "avr"))]
+            value: core::sync::atomic::$name,
+        }
+
+        // SAFETY: The value is only accessed within critical sections, which exclude everything
+        // else on the single core of AVR.
+        #[cfg(target_arch = "avr")]
+        unsafe impl Sync for $name {}
+
+        impl $name {
+            /// Creates a new atomic integer.
+            #[inline]
+            pub const fn new(value: $int) -> Self {
+                cfg_if::cfg_if! {
+                    if #[cfg(target_arch = "avr")] {
+                        Self {
+                            value: UnsafeCell::new(value),
+                        }
+                    } else {
+                        Self {
+                            value: core::sync::atomic::$name::new(value),
+                        }
+                    }
+                }
+            }
+
+            /// Returns a mutable reference to the underlying integer.
+            #[inline]
+            pub fn get_mut(&mut self) -> &mut $int {
+                self.value.get_mut()
+            }
+
+            /// Consumes the atomic and returns the contained value.
+            #[inline]
+            pub fn into_inner(self) -> $int {
+                self.value.into_inner()
+            }
+
+            /// Returns a pointer to the underlying integer.
+            ///
+            /// Accessing it is only atomic with interrupts disabled.
+            #[inline]
+            pub const fn as_ptr(&self) -> *mut $int {
+                cfg_if::cfg_if! {
+                    if #[cfg(target_arch = "avr")] {
+                        self.value.get()
+                    } else {
+                        self.value.as_ptr()
+                    }
+                }
+            }
+
+            /// Loads the value.
+            #[inline]
+            pub fn load(&self, _order: Ordering) -> $int {
+                cfg_if::cfg_if! {
+                    if #[cfg(target_arch = "avr")] {
+                        // SAFETY: Interrupts are disabled, so nothing else accesses the value.
+                        crate::interrupt::free(|_| unsafe { *self.value.get() })
+                    } else {
+                        self.value.load(Ordering::SeqCst)
+                    }
+                }
+            }
+
+            /// Stores `value`.
+            #[inline]
+            pub fn store(&self, value: $int, _order: Ordering) {
+                cfg_if::cfg_if! {
+                    if #[cfg(target_arch = "avr")] {
+                        // SAFETY: Interrupts are disabled, so nothing else accesses the value.
+                        crate::interrupt::free(|_| unsafe { *self.value.get() = value })
+                    } else {
+                        self.value.store(value, Ordering::SeqCst)
+                    }
+                }
+            }
+
+            /// Stores `value` and returns the previous value.
+            #[inline]
+            pub fn swap(&self, value: $int, _order: Ordering) -> $int {
+                self.update(|_| value)
+            }
+
+            /// Stores `new` if the current value is `current`.
+            ///
+            /// Returns the previous value, as `Ok` if it was `current` and `new` was stored.
+            #[inline]
+            pub fn compare_exchange(
+                &self,
+                current: $int,
+                new: $int,
+                _success: Ordering,
+                _failure: Ordering,
+            ) -> Result<$int, $int> {
+                cfg_if::cfg_if! {
+                    if #[cfg(target_arch = "avr")] {
+                        crate::interrupt::free(|_| {
+                            // SAFETY: Interrupts are disabled, so nothing else accesses the value.
+                            unsafe {
+                                match *self.value.get() {
+                                    previous if previous == current => {
+                                        *self.value.get() = new;
+                                        Ok(previous)
+                                    }
+                                    previous => Err(previous),
+                                }
+                            }
+                        })
+                    } else {
+                        self.value
+                            .compare_exchange(current, new, Ordering::SeqCst, Ordering::SeqCst)
+                    }
+                }
+            }
+
+            /// Stores `new` if the current value is `current`, see
+            #[doc = concat!("[`compare_exchange()`](", stringify!($name), "::compare_exchange).")]
+            ///
+            /// Unlike on other architectures, this never fails spuriously.
+            #[inline]
+            pub fn compare_exchange_weak(
+                &self,
+                current: $int,
+                new: $int,
+                success: Ordering,
+                failure: Ordering,
+            ) -> Result<$int, $int> {
+                self.compare_exchange(current, new, success, failure)
+            }
+
+            /// Adds `value` to the current value, wrapping around on overflow, and returns the
+            /// previous value.
+            #[inline]
+            pub fn fetch_add(&self, value: $int, _order: Ordering) -> $int {
+                self.update(|current| current.wrapping_add(value))
+            }
+
+            /// Subtracts `value` from the current value, wrapping around on overflow, and returns
+            /// the previous value.
+            #[inline]
+            pub fn fetch_sub(&self, value: $int, _order: Ordering) -> $int {
+                self.update(|current| current.wrapping_sub(value))
+            }
+
+            /// Bitwise "and" with the current value, returns the previous value.
+            #[inline]
+            pub fn fetch_and(&self, value: $int, _order: Ordering) -> $int {
+                self.update(|current| current & value)
+            }
+
+            /// Bitwise "nand" with the current value, returns the previous value.
+            #[inline]
+            pub fn fetch_nand(&self, value: $int, _order: Ordering) -> $int {
+                self.update(|current| !(current & value))
+            }
+
+            /// Bitwise "or" with the current value, returns the previous value.
+            #[inline]
+            pub fn fetch_or(&self, value: $int, _order: Ordering) -> $int {
+                self.update(|current| current | value)
+            }
+
+            /// Bitwise "xor" with the current value, returns the previous value.
+            #[inline]
+            pub fn fetch_xor(&self, value: $int, _order: Ordering) -> $int {
+                self.update(|current| current ^ value)
+            }
+
+            /// Stores the maximum of the current value and `value`, returns the previous value.
+            #[inline]
+            pub fn fetch_max(&self, value: $int, _order: Ordering) -> $int {
+                self.update(|current| current.max(value))
+            }
+
+            /// Stores the minimum of the current value and `value`, returns the previous value.
+            #[inline]
+            pub fn fetch_min(&self, value: $int, _order: Ordering) -> $int {
+                self.update(|current| current.min(value))
+            }
+
+            /// Stores the value returned by `f`, unless it returns `None`.
+            ///
+            /// `f` is called with interrupts enabled, so it may be called again if an interrupt
+            /// handler changed the value meanwhile.  Returns the previous value, as `Ok` if `f`
+            /// returned a new value.
+            #[inline]
+            pub fn fetch_update<F>(
+                &self,
+                set_order: Ordering,
+                fetch_order: Ordering,
+                mut f: F,
+            ) -> Result<$int, $int>
+            where
+                F: FnMut($int) -> Option<$int>,
+            {
+                let mut previous = self.load(fetch_order);
+                while let Some(new) = f(previous) {
+                    match self.compare_exchange(previous, new, set_order, fetch_order) {
+                        Ok(previous) => return Ok(previous),
+                        Err(current) => previous = current,
+                    }
+                }
+                Err(previous)
+            }
+
+            // Replaces the value by `f(value)` atomically, i.e. with interrupts disabled on AVR,
+            // and returns the previous value.
+            #[inline(always)]
+            fn update(&self, f: impl Fn($int) -> $int) -> $int {
+                cfg_if::cfg_if! {
+                    if #[cfg(target_arch = "avr")] {
+                        crate::interrupt::free(|_| {
+                            // SAFETY: Interrupts are disabled, so nothing else accesses the value.
+                            unsafe {
+                                let previous = *self.value.get();
+                                *self.value.get() = f(previous);
+                                previous
+                            }
+                        })
+                    } else {
+                        self.value
+                            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |previous| {
+                                Some(f(previous))
+                            })
+                            .unwrap_or_else(|previous| previous)
+                    }
+                }
+            }
+        }
+
+        impl Default for $name {
+            #[inline]
+            fn default() -> Self {
+                Self::new(0)
+            }
+        }
+
+        impl From<$int> for $name {
+            #[inline]
+            fn from(value: $int) -> Self {
+                Self::new(value)
+            }
+        }
+
+        impl core::fmt::Debug for $name {
+            fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                core::fmt::Debug::fmt(&self.load(Ordering::Relaxed), f)
+            }
+        }
+    };
+}
+
+atomic_int!(AtomicU16, u16);
+atomic_int!(AtomicI16, i16);
+atomic_int!(AtomicU32, u32);
+atomic_int!(AtomicI32, i32);
+atomic_int!(AtomicUsize, usize);
+atomic_int!(AtomicIsize, isize);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compare_exchange() {
+        let atomic = AtomicU32::new(1);
+        let order = Ordering::SeqCst;
+        assert_eq!(atomic.compare_exchange(1, 2, order, order), Ok(1));
+        assert_eq!(atomic.load(order), 2);
+        assert_eq!(atomic.compare_exchange(1, 3, order, order), Err(2));
+        assert_eq!(atomic.load(order), 2);
+        assert_eq!(atomic.compare_exchange_weak(2, 3, order, order), Ok(2));
+        assert_eq!(atomic.into_inner(), 3);
+    }
+
+    #[test]
+    fn fetch_update_retries() {
+        let atomic = AtomicI16::new(10);
+        let mut calls = 0;
+        let result = atomic.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |value| {
+            calls += 1;
+            // Changes the value behind the back of the first attempt, like an interrupt handler.
+            if calls == 1 {
+                atomic.store(20, Ordering::SeqCst);
+            }
+            Some(value + 1)
+        });
+        assert_eq!(result, Ok(20));
+        assert_eq!(calls, 2);
+        assert_eq!(atomic.load(Ordering::SeqCst), 21);
+
+        let result = atomic.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |_| None);
+        assert_eq!(result, Err(21));
+        assert_eq!(atomic.load(Ordering::SeqCst), 21);
+    }
+
+    #[test]
+    fn fetch_add_and_sub_wrap_around() {
+        let atomic = AtomicU16::new(u16::MAX);
+        assert_eq!(atomic.fetch_add(2, Ordering::SeqCst), u16::MAX);
+        assert_eq!(atomic.fetch_sub(3, Ordering::SeqCst), 1);
+        assert_eq!(atomic.load(Ordering::SeqCst), u16::MAX - 1);
+
+        let atomic = AtomicI32::new(i32::MIN);
+        assert_eq!(atomic.fetch_sub(1, Ordering::SeqCst), i32::MIN);
+        assert_eq!(atomic.fetch_add(1, Ordering::SeqCst), i32::MAX);
+        assert_eq!(atomic.load(Ordering::SeqCst), i32::MIN);
+    }
+}
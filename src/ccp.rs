@@ -0,0 +1,131 @@
+//! Configuration Change Protection
+//!
+//! Some registers of newer AVR chips (and a few classic ones like the ATtiny841) are protected
+//! against accidental writes, e.g. `CLKCTRL.MCLKCTRLB`, `WDT.CTRLA` or `RSTCTRL.SWRR`.  Writing
+//! them requires writing a signature to the `CCP` register first, followed by the protected
+//! register within the next four instructions.  The `write()` method of a register can't
+//! guarantee this timing, so this module does both writes in assembly:
+//!
+//! ```ignore
+//! let dp = avr_device::atmega4809::Peripherals::take().unwrap();
+//!
+//! // Run the CPU from the main clock without prescaler.
+//! unsafe { avr_device::ccp::protected_write(dp.clkctrl.mclkctrlb(), 0x00) };
+//! ```
+//!
+//! Each chip's module has a `CCP` constant with the address of its `CCP` register, which is `None`
+//! for chips without configuration change protection.  `protected_write()` and `spm_write()` are
+//! only available when exactly one MCU feature is selected, and fail to compile for a chip
+//! without `CCP` register.
+
+#[cfg(target_arch = "avr")]
+use core::arch::asm;
+
+/// Signature which unlocks protected I/O registers.
+pub const IOREG: u8 = 0xd8;
+
+/// Signature which unlocks self-programming, i.e. writing NVM commands to `NVMCTRL.CTRLA` on
+/// newer chips.
+pub const SPM: u8 = 0x9d;
+
+/// Writes `value` to the protected I/O register `reg`.
+///
+/// This function is only available with a single selected MCU.
+///
+/// # Safety
+///
+/// As with the `bits()` method of a register, `value` must be valid for the register.  Protected
+/// registers control things like the clock source or a reset, so this can have drastic effects
+/// on the program.
+#[cfg(avr_device_single_mcu)]
+#[inline(always)]
+pub unsafe fn protected_write<REG>(reg: &crate::generic::Reg<REG>, value: u8)
+where
+    REG: crate::generic::RegisterSpec<Ux = u8> + crate::generic::Writable,
+{
+    write(Ccp::<REG>::ADDRESS, reg.as_ptr(), IOREG, value)
+}
+
+/// Writes `value` to the register `reg` which is protected by the SPM signature, e.g. an NVM
+/// command to `NVMCTRL.CTRLA`.
+///
+/// This function is only available with a single selected MCU.
+///
+/// # Safety
+///
+/// As with the `bits()` method of a register, `value` must be valid for the register.  NVM
+/// commands can erase or overwrite flash and EEPROM contents.
+#[cfg(avr_device_single_mcu)]
+#[inline(always)]
+pub unsafe fn spm_write<REG>(reg: &crate::generic::Reg<REG>, value: u8)
+where
+    REG: crate::generic::RegisterSpec<Ux = u8> + crate::generic::Writable,
+{
+    write(Ccp::<REG>::ADDRESS, reg.as_ptr(), SPM, value)
+}
+
+// Address of the `CCP` register, evaluated only when one of the functions above is used.
+#[cfg(avr_device_single_mcu)]
+struct Ccp<REG>(core::marker::PhantomData<REG>);
+
+#[cfg(avr_device_single_mcu)]
+impl<REG> Ccp<REG> {
+    const ADDRESS: u16 = match crate::selected::CCP {
+        Some(address) => address,
+        None => panic!("This chip has no configuration change protection"),
+    };
+}
+
+/// Writes `signature` to the `CCP` register at address `ccp`, followed by `value` to `register`.
+#[cfg(avr_device_single_mcu)]
+#[inline(always)]
+pub(crate) unsafe fn write(ccp: u16, register: *mut u8, signature: u8, value: u8) {
+    cfg_if::cfg_if! {
+        if #[cfg(target_arch = "avr")] {
+            // The CPU ignores interrupts during the protected period, so only the instructions
+            // themselves need to be back to back.
+            asm!(
+                "st X, {signature}",
+                "st Z, {value}",
+                signature = in(reg) signature,
+                value = in(reg) value,
+                in("X") ccp,
+                in("Z") register,
+                options(nostack, preserves_flags),
+            );
+        } else if #[cfg(feature = "host-mock")] {
+            crate::register(ccp).write_volatile(signature);
+            register.write_volatile(value);
+        } else {
+            let _ = (ccp, register, signature, value);
+            unimplemented!()
+        }
+    }
+}
+
+/// Executes the NVM controller `command` by writing it to `NVMCTRL.CTRLA` at address `ctrla`.
+///
+/// Chips with an NVM controller always have configuration change protection.
+#[cfg(avr_device_single_mcu)]
+#[inline(always)]
+pub(crate) unsafe fn nvm_command(ctrla: u16, command: u8) {
+    if let Some(ccp) = crate::selected::CCP {
+        write(ccp, crate::register(ctrla), SPM, command);
+    }
+}
+
+#[cfg(all(test, feature = "host-mock", avr_device_single_mcu))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_unlocks_the_register() {
+        // Chips without `CCP` don't call `write()`, so any address will do for them.
+        let ccp = crate::selected::CCP.unwrap_or(0x34);
+        let mut registers = crate::selected::REGISTER_FILE.lock();
+        registers.reset();
+
+        unsafe { write(ccp, crate::register(ccp + 1), IOREG, 0x5a) };
+        assert_eq!(registers.changes(), [(ccp, IOREG), (ccp + 1, 0x5a)]);
+    }
+}
@@ -0,0 +1,129 @@
+//! CPU Interrupt Controller
+//!
+//! Newer chips (tinyAVR 0/1-series, megaAVR 0-series and AVR Dx) have a CPU interrupt controller
+//! (`CPUINT`), which can raise one interrupt to level 1.  Its handler preempts the handlers of all
+//! other interrupts, which stay at level 0:
+//!
+//! ```ignore
+//! use avr_device::atmega4809::Interrupt;
+//! use avr_device::cpuint;
+//!
+//! #[avr_device::interrupt(atmega4809, level1)]
+//! fn TCB0_INT() {
+//!     // Runs even while another interrupt handler is running
+//! }
+//!
+//! // SAFETY: The handler of `TCB0_INT` is declared with `level1`.
+//! unsafe { cpuint::set_level1(Some(Interrupt::TCB0_INT)) };
+//! cpuint::set_round_robin(true);
+//! ```
+//!
+//! On these chips, the global interrupt flag stays set while a handler runs, and only the
+//! controller keeps other level 0 interrupts from being serviced.  State shared between a level 0
+//! handler and the level 1 handler still needs a [critical section](crate::interrupt::free).
+//!
+//! Each chip's module has a `CPUINT` constant describing its interrupt controller, `None` on
+//! classic chips.  The functions of this module are only available when exactly one MCU feature
+//! is selected, and panic on chips without an interrupt controller.
+
+/// Description of the CPU interrupt controller of a chip.
+///
+/// Each chip's module has a `CPUINT` constant of this type, generated from the chip's vendor
+/// description.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InterruptController {
+    /// Address of the protected `CPUINT.CTRLA` in the data space.
+    pub ctrla: u16,
+    /// Address of `CPUINT.LVL1VEC` in the data space.
+    pub lvl1vec: u16,
+    /// Mask of `LVL0RR` in `CPUINT.CTRLA`.
+    pub round_robin: u8,
+    /// Mask of `CVT` in `CPUINT.CTRLA`.
+    pub compact_vector_table: u8,
+}
+
+/// Raises `interrupt` to level 1, or lowers the current level 1 interrupt back to level 0 if
+/// `interrupt` is `None`.
+///
+/// This function is only available with a single selected MCU.
+///
+/// # Safety
+///
+/// - The handler of `interrupt` must be declared with the `level1` option of
+///   [`#[avr_device::interrupt]`](macro@crate::interrupt).  Otherwise, a handler serving several
+///   vectors could be preempted by itself, breaking its `static mut` resources.
+#[cfg(avr_device_single_mcu)]
+#[inline]
+pub unsafe fn set_level1(interrupt: Option<crate::selected::Interrupt>) {
+    // Vector 0 is the reset vector, so it disables level 1.
+    let vector = interrupt.map_or(0, |interrupt| interrupt.vector_number());
+    crate::register(controller().lvl1vec).write_volatile(vector);
+}
+
+/// Returns the interrupt which is currently raised to level 1, if any.
+///
+/// This function is only available with a single selected MCU.
+#[cfg(avr_device_single_mcu)]
+#[inline]
+pub fn level1() -> Option<crate::selected::Interrupt> {
+    // SAFETY: The address belongs to the selected MCU.
+    match unsafe { crate::register(controller().lvl1vec).read_volatile() } {
+        0 => None,
+        vector => crate::selected::Interrupt::from_vector(vector),
+    }
+}
+
+/// Enables or disables round-robin scheduling of level 0 interrupts.
+///
+/// Normally, pending level 0 interrupts are serviced in the order of their vector numbers, so an
+/// interrupt which fires often can starve those with higher numbers.  With round-robin
+/// scheduling, the interrupt serviced last gets the lowest priority.
+///
+/// This function is only available with a single selected MCU.
+#[cfg(avr_device_single_mcu)]
+#[inline]
+pub fn set_round_robin(enabled: bool) {
+    // SAFETY: The bit only changes the order in which interrupts are serviced.
+    unsafe { modify_ctrla(controller().round_robin, enabled) }
+}
+
+/// Enables or disables the compact vector table.
+///
+/// With the compact vector table, all level 0 interrupts share vector 3, the level 1 interrupt
+/// uses vector 2 and the non-maskable interrupt vector 1.
+///
+/// This function is only available with a single selected MCU.
+///
+/// # Safety
+///
+/// - The handlers of the interrupts with the vector numbers 2 and 3 must serve all level 1 and
+///   level 0 interrupts respectively, as the handlers of other interrupts aren't called anymore.
+#[cfg(avr_device_single_mcu)]
+#[inline]
+pub unsafe fn set_compact_vector_table(enabled: bool) {
+    modify_ctrla(controller().compact_vector_table, enabled)
+}
+
+#[cfg(avr_device_single_mcu)]
+#[inline(always)]
+fn controller() -> InterruptController {
+    match crate::selected::CPUINT {
+        Some(controller) => controller,
+        None => panic!("This chip has no CPU interrupt controller"),
+    }
+}
+
+// Sets or clears `mask` in the protected `CPUINT.CTRLA`.
+#[cfg(avr_device_single_mcu)]
+unsafe fn modify_ctrla(mask: u8, set: bool) {
+    let ctrla = crate::register(controller().ctrla);
+    crate::interrupt::free(|_| {
+        let value = match set {
+            true => ctrla.read_volatile() | mask,
+            false => ctrla.read_volatile() & !mask,
+        };
+        if let Some(ccp) = crate::selected::CCP {
+            crate::ccp::write(ccp, ctrla, crate::ccp::IOREG, value);
+        }
+    })
+}
@@ -0,0 +1,397 @@
+//! EEPROM
+//!
+//! Byte and block access to the EEPROM of the selected chip:
+//!
+//! ```ignore
+//! use avr_device::eeprom;
+//!
+//! let boot_count = eeprom::read_byte(0).unwrap();
+//! eeprom::update_byte(0, boot_count.wrapping_add(1)).unwrap();
+//!
+//! let mut name = [0; 16];
+//! eeprom::read(0x10, &mut name).unwrap();
+//! ```
+//!
+//! Writes wait for the previous write to finish and return while the EEPROM is still being
+//! programmed.  [`update()`] and [`update_byte()`] only program bytes which differ from the new
+//! data, saving both time and EEPROM wear, and skip the erase or the write phase if it isn't
+//! needed and the chip supports this.  All accesses are checked against the chip's `EEPROM_SIZE`.
+//!
+//! Each step of an access runs with interrupts disabled, so an interrupt handler using the EEPROM
+//! can't get in between.  Each chip's module has an `EEPROM` constant describing its EEPROM
+//! controller.  The functions of this module are only available when exactly one MCU feature is
+//! selected.
+
+#[cfg(all(target_arch = "avr", avr_device_single_mcu))]
+use core::arch::asm;
+
+/// Description of the EEPROM controller of a chip.
+///
+/// Each chip's module has an `EEPROM` constant of this type, generated from the chip's vendor
+/// description.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EepromController {
+    /// EEPROM accessed through `EECR`, `EEAR` and `EEDR` (classic megaAVR and tinyAVR chips).
+    Classic {
+        /// Address of `EECR` in the data space.
+        eecr: u16,
+        /// Address of `EEAR` (`EEARL`) in the data space.
+        eear: u16,
+        /// Address of `EEDR` in the data space.
+        eedr: u16,
+        /// Mask of `EEMPE` (`EEMWE` on older chips).
+        master_write_enable: u8,
+        /// Mask of `EEPE` (`EEWE` on older chips).
+        write_enable: u8,
+        /// Mask of `EERE`.
+        read_enable: u8,
+        /// Mask of the `EEPM` bits, zero if the chip always erases and writes at once.
+        programming_mode: u8,
+    },
+    /// Memory mapped EEPROM, programmed through a page buffer (tinyAVR 0/1-series and megaAVR
+    /// 0-series).
+    PageBuffer {
+        /// Address of `NVMCTRL.CTRLA` in the data space.
+        ctrla: u16,
+        /// Address of `NVMCTRL.STATUS` in the data space.
+        status: u16,
+        /// Mask of `EEBUSY` in `NVMCTRL.STATUS`.
+        busy: u8,
+        /// Command which erases and writes the bytes loaded into the page buffer.
+        erase_write: u8,
+        /// Command which erases the bytes loaded into the page buffer.
+        erase: u8,
+        /// Command which writes the bytes loaded into the page buffer without erasing them.
+        write: u8,
+    },
+    /// Memory mapped EEPROM, programmed byte by byte after selecting a command (AVR Dx).
+    Command {
+        /// Address of `NVMCTRL.CTRLA` in the data space.
+        ctrla: u16,
+        /// Address of `NVMCTRL.STATUS` in the data space.
+        status: u16,
+        /// Mask of `EEBUSY` in `NVMCTRL.STATUS`.
+        busy: u8,
+        /// Command under which stores erase and write a byte.
+        erase_write: u8,
+        /// Command under which stores erase a byte.
+        erase: u8,
+        /// Command under which stores write a byte without erasing it.
+        write: u8,
+        /// Command which ends programming.
+        none: u8,
+    },
+}
+
+/// Error returned when an access doesn't fit into the EEPROM.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+pub struct OutOfBounds;
+
+// Programming operations.  Erasing sets all bits of a byte, writing can only clear bits.
+#[cfg(avr_device_single_mcu)]
+#[derive(Clone, Copy)]
+enum Operation {
+    EraseWrite,
+    Erase,
+    Write,
+}
+
+/// Returns whether the EEPROM is currently being programmed.
+///
+/// This function is only available with a single selected MCU.
+#[cfg(avr_device_single_mcu)]
+#[inline]
+pub fn is_busy() -> bool {
+    // SAFETY: The addresses belong to the selected MCU.
+    unsafe {
+        match crate::selected::EEPROM {
+            EepromController::Classic {
+                eecr, write_enable, ..
+            } => crate::register(eecr).read_volatile() & write_enable != 0,
+            EepromController::PageBuffer { status, busy, .. }
+            | EepromController::Command { status, busy, .. } => {
+                crate::register(status).read_volatile() & busy != 0
+            }
+        }
+    }
+}
+
+/// Reads the byte at `address`.
+///
+/// This function is only available with a single selected MCU.
+#[cfg(avr_device_single_mcu)]
+pub fn read_byte(address: u16) -> Result<u8, OutOfBounds> {
+    check_bounds(address, 1)?;
+    // SAFETY: The address is within the EEPROM.
+    Ok(unsafe { read_unchecked(address) })
+}
+
+/// Erases and writes the byte at `address`.
+///
+/// This function is only available with a single selected MCU.
+#[cfg(avr_device_single_mcu)]
+pub fn write_byte(address: u16, value: u8) -> Result<(), OutOfBounds> {
+    check_bounds(address, 1)?;
+    // SAFETY: The address is within the EEPROM.
+    unsafe { program(address, value, Operation::EraseWrite) };
+    Ok(())
+}
+
+/// Programs the byte at `address`, if it differs from `value`.
+///
+/// This function is only available with a single selected MCU.
+#[cfg(avr_device_single_mcu)]
+pub fn update_byte(address: u16, value: u8) -> Result<(), OutOfBounds> {
+    check_bounds(address, 1)?;
+    // SAFETY: The address is within the EEPROM.
+    unsafe { update_unchecked(address, value) };
+    Ok(())
+}
+
+/// Reads `buffer.len()` bytes starting at `address` into `buffer`.
+///
+/// This function is only available with a single selected MCU.
+#[cfg(avr_device_single_mcu)]
+pub fn read(address: u16, buffer: &mut [u8]) -> Result<(), OutOfBounds> {
+    check_bounds(address, buffer.len())?;
+    for (address, byte) in (address..).zip(buffer) {
+        // SAFETY: The whole range is within the EEPROM.
+        *byte = unsafe { read_unchecked(address) };
+    }
+    Ok(())
+}
+
+/// Erases and writes `data` starting at `address`.
+///
+/// This function is only available with a single selected MCU.
+#[cfg(avr_device_single_mcu)]
+pub fn write(address: u16, data: &[u8]) -> Result<(), OutOfBounds> {
+    check_bounds(address, data.len())?;
+    for (address, value) in (address..).zip(data) {
+        // SAFETY: The whole range is within the EEPROM.
+        unsafe { program(address, *value, Operation::EraseWrite) };
+    }
+    Ok(())
+}
+
+/// Programs the bytes of `data` starting at `address` which differ from the EEPROM contents.
+///
+/// This function is only available with a single selected MCU.
+#[cfg(avr_device_single_mcu)]
+pub fn update(address: u16, data: &[u8]) -> Result<(), OutOfBounds> {
+    check_bounds(address, data.len())?;
+    for (address, value) in (address..).zip(data) {
+        // SAFETY: The whole range is within the EEPROM.
+        unsafe { update_unchecked(address, *value) };
+    }
+    Ok(())
+}
+
+#[cfg(avr_device_single_mcu)]
+fn check_bounds(address: u16, len: usize) -> Result<(), OutOfBounds> {
+    match (address as usize).checked_add(len) {
+        Some(end) if end <= crate::selected::EEPROM_SIZE as usize => Ok(()),
+        _ => Err(OutOfBounds),
+    }
+}
+
+// Waits until the EEPROM is ready and then runs `f` with interrupts disabled.  Interrupts are
+// enabled in between polls, so waiting for a write doesn't block them.
+#[cfg(avr_device_single_mcu)]
+fn when_ready<R>(f: impl FnOnce() -> R) -> R {
+    let mut f = Some(f);
+    loop {
+        let result = crate::interrupt::free(|_| match is_busy() {
+            true => None,
+            false => f.take().map(|f| f()),
+        });
+        if let Some(result) = result {
+            return result;
+        }
+    }
+}
+
+// Address of an EEPROM byte in the data space, on chips which map the EEPROM.
+#[cfg(avr_device_single_mcu)]
+fn mapped(address: u16) -> *mut u8 {
+    match crate::selected::MAPPED_EEPROM_START {
+        Some(start) => crate::register(start + address),
+        None => unreachable!(),
+    }
+}
+
+#[cfg(avr_device_single_mcu)]
+unsafe fn set_address(eear: u16, address: u16) {
+    crate::register(eear).write_volatile(address as u8);
+    if crate::selected::EEPROM_SIZE > 0x100 {
+        crate::register(eear + 1).write_volatile((address >> 8) as u8);
+    }
+}
+
+#[cfg(avr_device_single_mcu)]
+unsafe fn read_unchecked(address: u16) -> u8 {
+    when_ready(|| match crate::selected::EEPROM {
+        EepromController::Classic {
+            eecr,
+            eear,
+            eedr,
+            read_enable,
+            ..
+        } => {
+            set_address(eear, address);
+            let eecr = crate::register(eecr);
+            eecr.write_volatile(eecr.read_volatile() | read_enable);
+            crate::register(eedr).read_volatile()
+        }
+        EepromController::PageBuffer { .. } | EepromController::Command { .. } => {
+            mapped(address).read_volatile()
+        }
+    })
+}
+
+#[cfg(avr_device_single_mcu)]
+unsafe fn update_unchecked(address: u16, value: u8) {
+    let current = read_unchecked(address);
+    let operation = if value == current {
+        return;
+    } else if value & !current == 0 {
+        Operation::Write
+    } else if value == 0xff {
+        Operation::Erase
+    } else {
+        Operation::EraseWrite
+    };
+    program(address, value, operation)
+}
+
+#[cfg(avr_device_single_mcu)]
+unsafe fn program(address: u16, value: u8, operation: Operation) {
+    when_ready(|| match crate::selected::EEPROM {
+        EepromController::Classic {
+            eecr,
+            eear,
+            eedr,
+            master_write_enable,
+            write_enable,
+            read_enable,
+            programming_mode,
+        } => {
+            set_address(eear, address);
+            crate::register(eedr).write_volatile(value);
+
+            // `EEPM` is `0b01` for erasing only and `0b10` for writing only.
+            let mode_lsb = programming_mode & programming_mode.wrapping_neg();
+            let mode = match operation {
+                Operation::EraseWrite => 0,
+                Operation::Erase => mode_lsb,
+                Operation::Write => mode_lsb << 1,
+            };
+            let clear = programming_mode | master_write_enable | write_enable | read_enable;
+            let eecr_value = (crate::register(eecr).read_volatile() & !clear) | mode;
+            crate::register(eecr).write_volatile(eecr_value);
+
+            cfg_if::cfg_if! {
+                if #[cfg(target_arch = "avr")] {
+                    // `EEPE` must be set within four cycles after `EEMPE`.
+                    asm!(
+                        "st Z, {master}",
+                        "st Z, {start}",
+                        master = in(reg) eecr_value | master_write_enable,
+                        start = in(reg) eecr_value | master_write_enable | write_enable,
+                        in("Z") eecr,
+                        options(nostack, preserves_flags),
+                    );
+                } else if #[cfg(feature = "host-mock")] {
+                    // The mock programs the byte at once, so `EEMPE` and `EEPE` are cleared again
+                    // right away.
+                    let eecr = crate::register(eecr);
+                    eecr.write_volatile(eecr_value | master_write_enable);
+                    eecr.write_volatile(eecr_value | master_write_enable | write_enable);
+                    eecr.write_volatile(eecr_value);
+                } else {
+                    unimplemented!()
+                }
+            }
+        }
+        EepromController::PageBuffer {
+            ctrla,
+            erase_write,
+            erase,
+            write,
+            ..
+        } => {
+            // Only the bytes loaded into the page buffer are programmed.
+            mapped(address).write_volatile(value);
+            crate::ccp::nvm_command(
+                ctrla,
+                match operation {
+                    Operation::EraseWrite => erase_write,
+                    Operation::Erase => erase,
+                    Operation::Write => write,
+                },
+            );
+        }
+        EepromController::Command {
+            ctrla,
+            erase_write,
+            erase,
+            write,
+            none,
+            ..
+        } => {
+            crate::ccp::nvm_command(
+                ctrla,
+                match operation {
+                    Operation::EraseWrite => erase_write,
+                    Operation::Erase => erase,
+                    Operation::Write => write,
+                },
+            );
+            mapped(address).write_volatile(value);
+            crate::ccp::nvm_command(ctrla, none);
+        }
+    })
+}
+
+#[cfg(all(test, feature = "host-mock", avr_device_single_mcu))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_byte_programs_the_byte() {
+        let mut registers = crate::selected::REGISTER_FILE.lock();
+        registers.reset();
+
+        write_byte(1, 0x5a).unwrap();
+        match crate::selected::EEPROM {
+            EepromController::Classic {
+                eecr, eear, eedr, ..
+            } => {
+                assert_eq!(registers.get(eear), 1);
+                assert_eq!(registers.get(eedr), 0x5a);
+                // Erasing and writing at once needs no programming mode.
+                assert_eq!(registers.get(eecr), 0);
+            }
+            EepromController::PageBuffer {
+                ctrla,
+                erase_write: command,
+                ..
+            }
+            | EepromController::Command {
+                ctrla,
+                none: command,
+                ..
+            } => {
+                let start = crate::selected::MAPPED_EEPROM_START.unwrap();
+                assert_eq!(registers.get(start + 1), 0x5a);
+                assert_eq!(registers.get(ctrla), command);
+            }
+        }
+        assert!(!is_busy());
+        assert_eq!(
+            write_byte(crate::selected::EEPROM_SIZE, 0),
+            Err(OutOfBounds)
+        );
+    }
+}
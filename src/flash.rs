@@ -0,0 +1,430 @@
+//! Flash Self-Programming
+//!
+//! Bootloaders, and programs which keep data in flash, can erase and write the flash of the chip
+//! they run on, one page of `PAGE_SIZE` bytes at a time:
+//!
+//! ```ignore
+//! use avr_device::flash;
+//!
+//! let mut page: flash::Page = [0xff; avr_device::atmega328p::PAGE_SIZE as usize];
+//! page[..4].copy_from_slice(b"AVR!");
+//! unsafe { flash::program_page(0x1000, &page) };
+//! ```
+//!
+//! [`program_page()`] runs all steps needed to program a page.  They are also available on their
+//! own: [`erase_page()`] sets all bytes of a page to `0xff`, [`fill_page_buffer()`] loads a word
+//! into the page buffer and [`write_page()`] programs the page buffer into a page.  On classic
+//! chips with a boot section, the application section can't be read while it is being
+//! programmed, and [`enable_rww()`] must be called before running the application again.
+//!
+//! Each operation waits until the flash and the EEPROM are ready, and runs with interrupts
+//! disabled until it is finished, as the interrupt vectors may be in the flash being programmed.
+//! Each chip's module has a `FLASH` constant describing its flash controller.  The functions of
+//! this module are only available when exactly one MCU feature is selected.
+//!
+//! # Boot Section
+//!
+//! Chips with a boot section restrict self-programming: Classic chips only execute `spm` from the
+//! boot section at the end of the flash, newer chips only let code in the boot section at the
+//! start of the flash write the application.  The `BOOT_SECTION` constant of a chip's module
+//! describes where the boot section is, but this crate doesn't place any code there.  That is up
+//! to the linker options of the bootloader or application.  For an ATmega328P with `BOOTSZ` set
+//! to a 4 KiB boot section, the bootloader is linked with:
+//!
+//! ```text
+//! -C link-arg=-Wl,--section-start=.text=0x7000
+//! ```
+//!
+//! On an ATmega4809 with a 2 KiB boot section (`BOOTEND` = 8), the bootloader is linked normally
+//! and the application with `.text` starting at `0x800` instead.
+
+#[cfg(target_arch = "avr")]
+use core::arch::asm;
+
+/// Description of the flash controller of a chip.
+///
+/// Each chip's module has a `FLASH` constant of this type, generated from the chip's vendor
+/// description.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlashController {
+    /// Flash programmed by `spm` after writing a command to `SPMCSR` (classic megaAVR and tinyAVR
+    /// chips).
+    Spm {
+        /// Address of `SPMCSR` (`SPMCR` on older chips) in the data space.
+        spmcsr: u16,
+        /// Mask of `SPMEN` (`SELFPRGEN` on some chips).
+        enable: u8,
+        /// Mask of `PGERS`.
+        page_erase: u8,
+        /// Mask of `PGWRT`.
+        page_write: u8,
+        /// Mask of `RWWSRE`, zero if the chip has no read-while-write section.
+        rww_enable: u8,
+    },
+    /// Flash programmed through a page buffer filled through the mapped flash (tinyAVR 0/1-series
+    /// and megaAVR 0-series).
+    PageBuffer {
+        /// Address of `NVMCTRL.CTRLA` in the data space.
+        ctrla: u16,
+        /// Address of `NVMCTRL.STATUS` in the data space.
+        status: u16,
+        /// Mask of `FBUSY` in `NVMCTRL.STATUS`.
+        busy: u8,
+        /// Start address of the flash in the data space.
+        mapped: u16,
+        /// Command which writes the page buffer.
+        write: u8,
+        /// Command which erases the page of the page buffer.
+        erase: u8,
+        /// Command which clears the page buffer.
+        clear_buffer: u8,
+    },
+    /// Flash programmed word by word by `spm` after selecting a command (AVR DU).
+    Command {
+        /// Address of `NVMCTRL.CTRLA` in the data space.
+        ctrla: u16,
+        /// Address of `NVMCTRL.STATUS` in the data space.
+        status: u16,
+        /// Mask of `FLBUSY` in `NVMCTRL.STATUS`.
+        busy: u8,
+        /// Command under which `spm` writes a word.
+        write: u8,
+        /// Command under which `spm` erases a page.
+        erase: u8,
+        /// Command which ends programming.
+        none: u8,
+    },
+    /// The chip can't program its own flash.
+    Unavailable,
+}
+
+/// Location of the boot section of a chip.
+///
+/// Each chip's module has a `BOOT_SECTION` constant of this type, generated from the chip's
+/// vendor description.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BootSection {
+    /// The boot section is at the end of the flash and its size is set by the `BOOTSZ` fuses
+    /// (classic megaAVR chips).
+    End {
+        /// Start addresses of the boot section for each size, from the smallest to the largest
+        /// boot section.
+        starts: &'static [u32],
+    },
+    /// The boot section is at the start of the flash and is followed by the application, its size
+    /// in blocks is set by the `BOOTEND` or `BOOTSIZE` fuse (newer chips).
+    Start {
+        /// Size of a block in bytes.
+        block_size: u16,
+    },
+    /// The chip has no separate boot section.
+    None,
+}
+
+impl BootSection {
+    /// Returns the start address of the application for a boot section of `blocks` blocks.
+    ///
+    /// Only chips which have their boot section at the start of the flash move the application.
+    #[inline]
+    pub const fn application_start(&self, blocks: u8) -> u32 {
+        match *self {
+            BootSection::Start { block_size } => blocks as u32 * block_size as u32,
+            BootSection::End { .. } | BootSection::None => 0,
+        }
+    }
+}
+
+/// Contents of a flash page of the selected MCU.
+#[cfg(avr_device_single_mcu)]
+pub type Page = [u8; crate::selected::PAGE_SIZE as usize];
+
+/// Returns whether the flash is currently being programmed.
+///
+/// This function is only available with a single selected MCU.
+#[cfg(avr_device_single_mcu)]
+#[inline]
+pub fn is_busy() -> bool {
+    // SAFETY: The addresses belong to the selected MCU.
+    unsafe {
+        match crate::selected::FLASH {
+            FlashController::Spm { spmcsr, enable, .. } => {
+                crate::register(spmcsr).read_volatile() & enable != 0
+            }
+            FlashController::PageBuffer { status, busy, .. }
+            | FlashController::Command { status, busy, .. } => {
+                crate::register(status).read_volatile() & busy != 0
+            }
+            FlashController::Unavailable => false,
+        }
+    }
+}
+
+/// Erases the page containing `address`, setting all of its bytes to `0xff`.
+///
+/// This function is only available with a single selected MCU.
+///
+/// # Safety
+///
+/// `address` must be within the flash, and the page must not contain code or data the program
+/// still uses.
+#[cfg(avr_device_single_mcu)]
+pub unsafe fn erase_page(address: u32) {
+    operation(|| match crate::selected::FLASH {
+        FlashController::Spm {
+            spmcsr,
+            enable,
+            page_erase,
+            ..
+        } => spm(Some((spmcsr, page_erase | enable)), address, 0xffff),
+        FlashController::PageBuffer {
+            ctrla,
+            mapped,
+            erase,
+            clear_buffer,
+            ..
+        } => {
+            // The page to erase is selected by loading a byte into the page buffer.
+            mapped_byte(mapped, address).write_volatile(0xff);
+            crate::ccp::nvm_command(ctrla, erase);
+            wait();
+            crate::ccp::nvm_command(ctrla, clear_buffer);
+        }
+        FlashController::Command {
+            ctrla, erase, none, ..
+        } => {
+            crate::ccp::nvm_command(ctrla, erase);
+            spm(None, address, 0xffff);
+            wait();
+            crate::ccp::nvm_command(ctrla, none);
+        }
+        FlashController::Unavailable => unavailable(),
+    })
+}
+
+/// Loads `word` into the page buffer at `address`.
+///
+/// Chips without a page buffer (AVR DU) write the word to the flash directly.
+///
+/// This function is only available with a single selected MCU.
+///
+/// # Safety
+///
+/// `address` must be the byte address of the word within the flash, so it must be even.  On chips
+/// without a page buffer, the word must be erased and must not be part of code or data the
+/// program still uses.
+#[cfg(avr_device_single_mcu)]
+pub unsafe fn fill_page_buffer(address: u32, word: u16) {
+    debug_assert!(address & 1 == 0, "The address of a word must be even");
+    operation(|| match crate::selected::FLASH {
+        FlashController::Spm { spmcsr, enable, .. } => spm(Some((spmcsr, enable)), address, word),
+        FlashController::PageBuffer { mapped, .. } => {
+            let [low, high] = word.to_le_bytes();
+            mapped_byte(mapped, address & !1).write_volatile(low);
+            mapped_byte(mapped, address | 1).write_volatile(high);
+        }
+        FlashController::Command {
+            ctrla, write, none, ..
+        } => {
+            crate::ccp::nvm_command(ctrla, write);
+            spm(None, address, word);
+            wait();
+            crate::ccp::nvm_command(ctrla, none);
+        }
+        FlashController::Unavailable => unavailable(),
+    })
+}
+
+/// Writes the page buffer into the page containing `address`.
+///
+/// The page must have been erased and the page buffer filled with the words of this page.  On
+/// chips without a page buffer (AVR DU), this does nothing.
+///
+/// This function is only available with a single selected MCU.
+///
+/// # Safety
+///
+/// `address` must be within the flash, and the page must not contain code or data the program
+/// still uses.
+#[cfg(avr_device_single_mcu)]
+pub unsafe fn write_page(address: u32) {
+    operation(|| match crate::selected::FLASH {
+        FlashController::Spm {
+            spmcsr,
+            enable,
+            page_write,
+            ..
+        } => spm(Some((spmcsr, page_write | enable)), address, 0xffff),
+        FlashController::PageBuffer { ctrla, write, .. } => crate::ccp::nvm_command(ctrla, write),
+        FlashController::Command { .. } => {}
+        FlashController::Unavailable => unavailable(),
+    })
+}
+
+/// Makes the read-while-write section readable again after erasing or writing it.
+///
+/// On chips without a read-while-write section, this does nothing.
+///
+/// This function is only available with a single selected MCU.
+#[cfg(avr_device_single_mcu)]
+pub fn enable_rww() {
+    if let FlashController::Spm {
+        spmcsr,
+        enable,
+        rww_enable,
+        ..
+    } = crate::selected::FLASH
+    {
+        if rww_enable != 0 {
+            // SAFETY: This doesn't change the flash contents.
+            operation(|| unsafe { spm(Some((spmcsr, rww_enable | enable)), 0, 0xffff) });
+        }
+    }
+}
+
+/// Erases the page containing `address` and programs it with `data`.
+///
+/// This function is only available with a single selected MCU.
+///
+/// # Safety
+///
+/// `address` must be within the flash, and the page must not contain code or data the program
+/// still uses.
+#[cfg(avr_device_single_mcu)]
+pub unsafe fn program_page(address: u32, data: &Page) {
+    let start = address - address % crate::selected::PAGE_SIZE as u32;
+    erase_page(start);
+    for (address, word) in (start..).step_by(2).zip(data.chunks_exact(2)) {
+        fill_page_buffer(address, u16::from_le_bytes([word[0], word[1]]));
+    }
+    write_page(start);
+    enable_rww();
+}
+
+// Waits until neither the flash nor the EEPROM are busy.
+#[cfg(avr_device_single_mcu)]
+fn wait() {
+    while is_busy() || crate::eeprom::is_busy() {}
+}
+
+// Runs `f` with interrupts disabled once the flash is ready, and waits until it is done.
+#[cfg(avr_device_single_mcu)]
+fn operation(f: impl FnOnce()) {
+    crate::interrupt::free(|_| {
+        wait();
+        f();
+        wait();
+    })
+}
+
+#[cfg(avr_device_single_mcu)]
+fn unavailable() -> ! {
+    panic!("This chip can't program its flash")
+}
+
+// Address of a flash byte in the data space.
+#[cfg(avr_device_single_mcu)]
+fn mapped_byte(mapped: u16, address: u32) -> *mut u8 {
+    crate::register((mapped as u32 + address) as u16)
+}
+
+// Executes `spm` with `word` in `r1:r0` and `address` in `Z` and `RAMPZ`.  With a `control`
+// register, the command is written to it right before.
+#[cfg(avr_device_single_mcu)]
+#[inline(always)]
+unsafe fn spm(control: Option<(u16, u8)>, address: u32, word: u16) {
+    cfg_if::cfg_if! {
+        if #[cfg(all(target_arch = "avr", target_feature = "spm"))] {
+            let [low, high] = word.to_le_bytes();
+            // `RAMPZ` is at I/O address 0x3b on all chips which have it.
+            #[cfg(target_feature = "elpm")]
+            asm!(
+                "out 0x3b, {page}",
+                page = in(reg) (address >> 16) as u8,
+                options(nomem, nostack, preserves_flags),
+            );
+            // `r0` and `r1` must be restored before the end of the block: `r0` is saved on the
+            // stack, `r1` is always zero.
+            match control {
+                // `spm` must follow within four cycles after writing the command.
+                Some((register, command)) => asm!(
+                    "push r0",
+                    "mov r0, {low}",
+                    "mov r1, {high}",
+                    "st X, {command}",
+                    "spm",
+                    "clr r1",
+                    "pop r0",
+                    low = in(reg) low,
+                    high = in(reg) high,
+                    command = in(reg) command,
+                    in("X") register,
+                    in("Z") address as u16,
+                ),
+                None => asm!(
+                    "push r0",
+                    "mov r0, {low}",
+                    "mov r1, {high}",
+                    "spm",
+                    "clr r1",
+                    "pop r0",
+                    low = in(reg) low,
+                    high = in(reg) high,
+                    in("Z") address as u16,
+                ),
+            }
+            // Other code expects `RAMPZ` to be zero.
+            #[cfg(target_feature = "elpm")]
+            asm!("out 0x3b, r1", options(nomem, nostack, preserves_flags));
+        } else if #[cfg(feature = "host-mock")] {
+            // The mock doesn't simulate the flash.  Like the hardware, it clears the command once
+            // `spm` is done.
+            if let Some((register, command)) = control {
+                let register = crate::register(register);
+                register.write_volatile(command);
+                register.write_volatile(0);
+            }
+            let _ = (address, word);
+        } else {
+            let _ = (control, address, word);
+            unimplemented!()
+        }
+    }
+}
+
+#[cfg(all(test, feature = "host-mock", avr_device_single_mcu))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn program_page_fills_and_writes_the_page() {
+        let mut registers = crate::selected::REGISTER_FILE.lock();
+        registers.reset();
+
+        let page: Page = core::array::from_fn(|i| i as u8);
+        match crate::selected::FLASH {
+            FlashController::Spm { spmcsr, .. } => {
+                unsafe { program_page(0, &page) };
+                // Each command is cleared once it is done.
+                assert_eq!(registers.get(spmcsr), 0);
+            }
+            FlashController::PageBuffer {
+                ctrla,
+                mapped,
+                write,
+                ..
+            } => {
+                unsafe { program_page(0, &page) };
+                for (address, byte) in (mapped..).zip(page) {
+                    assert_eq!(registers.get(address), byte);
+                }
+                assert_eq!(registers.get(ctrla), write);
+            }
+            FlashController::Command { ctrla, none, .. } => {
+                unsafe { program_page(0, &page) };
+                assert_eq!(registers.get(ctrla), none);
+            }
+            FlashController::Unavailable => return,
+        }
+        assert!(!is_busy());
+    }
+}
@@ -0,0 +1,73 @@
+//! Fuses and Lock Bits
+//!
+//! Each chip's module has a `fuses` module with a typed builder for every fuse and lock bit
+//! register, generated from the chip's vendor description.  The [`fuses!`](crate::fuses!) and
+//! [`lockbits!`](crate::lockbits!) macros place their values in the `.fuse` and `.lock` sections of
+//! the ELF file, from where programmers like `avrdude` can write them to the chip:
+//!
+//! ```ignore
+//! use avr_device::atmega328p::fuses::{Fuses, High, Low, SutCksel};
+//!
+//! avr_device::fuses!(Fuses {
+//!     low: Low::new()
+//!         .sut_cksel(SutCksel::Extxosc_8mhzXx_16kck_14ck_65ms)
+//!         .ckdiv8(false),
+//!     high: High::new().eesave(true),
+//!     ..Fuses::DEFAULT
+//! });
+//! ```
+//!
+//! Every register starts out with its factory default value.  Single bit settings take a `bool`
+//! which enables the setting when `true`.  On classic chips, this *programs* the fuse bit, i.e.
+//! clears it.  Settings with multiple bits take an enum of the documented values, or the raw
+//! value if there is none.  The variants are named after the vendor's names, which keep an
+//! underscore in front of each part starting with a digit, e.g.
+//! `Extxosc_0mhz4_0mhz9_1kck_14ck_65ms` selects an external crystal of 0.4 to 0.9 MHz with a
+//! start-up time of 1K CK / 14 CK + 65 ms.
+//!
+//! *Note*: Fuses and lock bits are not written by flashing the program alone, the programmer needs
+//! to be told to write them from the ELF file, too.  Wrong fuse values can render a chip
+//! unusable, e.g. by disabling the reset pin or selecting a clock source which isn't there.
+
+/// Places the fuse values in the `.fuse` section.
+///
+/// Takes the path of a chip's `Fuses` type, followed by the fields of its initializer.
+///
+/// ```ignore
+/// use avr_device::attiny85::fuses::{Fuses, Low};
+///
+/// avr_device::fuses!(Fuses {
+///     low: Low::new().ckdiv8(false),
+///     ..Fuses::DEFAULT
+/// });
+/// ```
+#[macro_export]
+macro_rules! fuses {
+    ($($fuses:ident)::+ { $($fields:tt)* }) => {
+        #[used]
+        #[link_section = ".fuse"]
+        static __AVR_DEVICE_FUSES: [u8; <$($fuses)::+>::SIZE] =
+            $($fuses)::+ { $($fields)* }.to_bytes();
+    };
+}
+
+/// Places the lock bit values in the `.lock` section.
+///
+/// Takes the path of a chip's `Lockbits` type, followed by the fields of its initializer.
+///
+/// ```ignore
+/// use avr_device::atmega328p::fuses::{Lb, Lockbit, Lockbits};
+///
+/// avr_device::lockbits!(Lockbits {
+///     lockbit: Lockbit::new().lb(Lb::ProgVerDisabled),
+/// });
+/// ```
+#[macro_export]
+macro_rules! lockbits {
+    ($($lockbits:ident)::+ { $($fields:tt)* }) => {
+        #[used]
+        #[link_section = ".lock"]
+        static __AVR_DEVICE_LOCKBITS: [u8; <$($lockbits)::+>::SIZE] =
+            $($lockbits)::+ { $($fields)* }.to_bytes();
+    };
+}
@@ -29,9 +29,39 @@
 //!     });
 //! }
 //! ```
+//!
+//! A critical section delays all interrupts.  If some state is only shared with a single
+//! interrupt handler, [crate::interrupt::mask] can instead disable just that interrupt, so all
+//! others keep being serviced:
+//!
+//! ```ignore
+//! use avr_device::atmega328p::Interrupt;
+//!
+//! avr_device::interrupt::mask(Interrupt::USART_RX, |masked| {
+//!     // The USART_RX handler can't run here
+//! });
+//! ```
+//!
+//! The `Interrupt` enum of each chip also knows the enable bit and the flag of each interrupt, so
+//! drivers can manage interrupts by name:
+//!
+//! ```ignore
+//! use avr_device::atmega328p::Interrupt;
+//!
+//! // Discard an overflow which happened while the interrupt was disabled.
+//! Interrupt::TIMER0_OVF.clear_pending();
+//! unsafe { Interrupt::TIMER0_OVF.enable() };
+//! assert!(Interrupt::TIMER0_OVF.is_enabled());
+//! ```
+//!
+//! To pass values from an interrupt handler to the main loop or the other way around, a [Queue]
+//! doesn't need a critical section at all.
 
 pub use bare_metal::{CriticalSection, Mutex};
 
+mod queue;
+pub use queue::{Consumer, Producer, Queue};
+
 #[cfg(target_arch = "avr")]
 use core::arch::asm;
 
@@ -80,6 +110,8 @@ pub fn disable() {
         if #[cfg(target_arch = "avr")] {
             // Disable interrupts
             unsafe { asm!("cli") };
+        } else if #[cfg(feature = "host-mock")] {
+            crate::mock::disable();
         } else {
             unimplemented!()
         }
@@ -97,7 +129,7 @@ pub fn disable() {
 /// Returns an object that contains the status of the global interrupt flag from *before* the `disable_save()` call.
 /// This object shall later be passed to the [crate::interrupt::restore] function.
 #[inline(always)]
-#[allow(unreachable_code)]
+#[allow(unreachable_code, clippy::needless_late_init)]
 pub fn disable_save() -> IrqFlag {
     let sreg;
     cfg_if::cfg_if! {
@@ -109,6 +141,10 @@ pub fn disable_save() -> IrqFlag {
                     sreg = out(reg) sreg,
                 )
             };
+        } else if #[cfg(feature = "host-mock")] {
+            // Other threads share the flag, so it is read and cleared in one step.
+            let _ = sreg;
+            return IrqFlag::new((crate::mock::disable_save() as u8) << 7);
         } else {
             let _ = sreg;
             unimplemented!()
@@ -139,6 +175,8 @@ pub unsafe fn enable() {
     cfg_if::cfg_if! {
         if #[cfg(target_arch = "avr")] {
             asm!("sei");
+        } else if #[cfg(feature = "host-mock")] {
+            crate::mock::enable();
         } else {
             unimplemented!()
         }
@@ -171,6 +209,8 @@ pub unsafe fn restore(irq_flag: IrqFlag) {
                 "out 0x3F, {sreg}",
                 sreg = in(reg) irq_flag.sreg,
             );
+        } else if #[cfg(feature = "host-mock")] {
+            crate::mock::restore(irq_flag.enabled());
         } else {
             let _ = irq_flag;
             unimplemented!()
@@ -187,7 +227,7 @@ pub unsafe fn restore(irq_flag: IrqFlag) {
 /// This function is **not** an optimization fence.
 /// That means memory accesses *can* be re-ordered by the compiler across this function call.
 #[inline(always)]
-#[allow(unreachable_code)]
+#[allow(unreachable_code, clippy::needless_late_init)]
 pub fn is_enabled() -> bool {
     let sreg;
     cfg_if::cfg_if! {
@@ -200,6 +240,8 @@ pub fn is_enabled() -> bool {
                     options(readonly, preserves_flags, nostack),
                 )
             };
+        } else if #[cfg(feature = "host-mock")] {
+            sreg = (crate::mock::interrupts_enabled() as u8) << 7;
         } else {
             let _ = sreg;
             unimplemented!()
@@ -218,7 +260,7 @@ where
     F: FnOnce(CriticalSection) -> R,
 {
     cfg_if::cfg_if! {
-        if #[cfg(target_arch = "avr")] {
+        if #[cfg(any(target_arch = "avr", feature = "host-mock"))] {
             // Disable interrupts. This is an optimization fence.
             let irq_flag = disable_save();
 
@@ -235,6 +277,197 @@ where
     }
 }
 
+/// Proof that an interrupt is masked, handed out by [crate::interrupt::mask].
+///
+/// Unless the interrupt's enable bits are unknown and all interrupts are disabled instead, only
+/// the handler of [`interrupt()`](Masked::interrupt) is kept from running.
+#[cfg(avr_device_single_mcu)]
+#[derive(Debug, Clone, Copy)]
+pub struct Masked<'m> {
+    interrupt: crate::selected::Interrupt,
+    _lifetime: core::marker::PhantomData<&'m ()>,
+}
+
+#[cfg(avr_device_single_mcu)]
+impl Masked<'_> {
+    /// Returns the masked interrupt.
+    #[inline(always)]
+    pub fn interrupt(&self) -> crate::selected::Interrupt {
+        self.interrupt
+    }
+}
+
+/// Execute closure `f` with only `interrupt` disabled.
+///
+/// This clears the interrupt's enable bits for the duration of `f` and then restores them, so a
+/// handler sharing state with `f` can't preempt it while all other interrupts are still serviced.
+/// An interrupt raised meanwhile stays pending and its handler runs after `f` returns.  If the
+/// chip's vendor description doesn't tell which bits enable the interrupt, `f` runs in a critical
+/// section instead.
+///
+/// `f` must not enable the interrupt itself, or configure the peripheral in a way that
+/// overwrites its enable bits.
+///
+/// This function is only available with a single selected MCU.
+#[cfg(avr_device_single_mcu)]
+#[inline]
+pub fn mask<F, R>(interrupt: crate::selected::Interrupt, f: F) -> R
+where
+    F: FnOnce(Masked<'_>) -> R,
+{
+    let masked = Masked {
+        interrupt,
+        _lifetime: core::marker::PhantomData,
+    };
+    let Some(bits) = interrupt.enable_bits() else {
+        return free(|_| f(masked));
+    };
+    let register = crate::register(bits.register);
+
+    // The register is only modified with interrupts disabled, as other interrupts' handlers may
+    // share it.  Flags in the register are written as zero, so they aren't cleared.
+    // SAFETY: The register belongs to the selected MCU and only the enable bits are changed.
+    let enabled = free(|_| unsafe {
+        let value = register.read_volatile();
+        register.write_volatile(value & !bits.flags & !bits.mask);
+        value & bits.mask
+    });
+
+    let r = f(masked);
+
+    // SAFETY: As above.
+    free(|_| unsafe {
+        let value = register.read_volatile() & !bits.flags & !bits.mask;
+        register.write_volatile(value | enabled);
+    });
+
+    r
+}
+
+/// Bits which enable an interrupt.
+///
+/// Each chip's `Interrupt` enum has an `enable_bits()` method returning these, generated from the
+/// chip's vendor description.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+pub struct EnableBits {
+    /// Address of the register with the enable bits in the data space.
+    pub register: u16,
+    /// Mask of the enable bits.  Some interrupts have several, e.g. one per event of the
+    /// peripheral which raises them.
+    pub mask: u8,
+    /// Mask of the interrupt flags in the same register, which are cleared by writing one.
+    pub flags: u8,
+}
+
+impl EnableBits {
+    /// Returns whether any of the enable bits is set.
+    ///
+    /// # Safety
+    ///
+    /// - The bits must belong to the chip the program runs on.
+    #[inline]
+    pub unsafe fn is_enabled(self) -> bool {
+        crate::register(self.register).read_volatile() & self.mask != 0
+    }
+
+    /// Sets the enable bits.
+    ///
+    /// # Safety
+    ///
+    /// - The bits must belong to the chip the program runs on.
+    /// - Do not call this function inside a [crate::interrupt::mask] of the interrupt.
+    #[inline]
+    pub unsafe fn enable(self) {
+        self.modify(|value| value | self.mask)
+    }
+
+    /// Clears the enable bits.
+    ///
+    /// # Safety
+    ///
+    /// - The bits must belong to the chip the program runs on.
+    #[inline]
+    pub unsafe fn disable(self) {
+        self.modify(|value| value & !self.mask)
+    }
+
+    // Modifies the register with interrupts disabled, as other interrupts' handlers may share it.
+    // The flags are written as zero, so they aren't cleared.
+    #[inline(always)]
+    unsafe fn modify(self, f: impl FnOnce(u8) -> u8) {
+        let register = crate::register(self.register);
+        free(|_| register.write_volatile(f(register.read_volatile() & !self.flags)))
+    }
+}
+
+/// Flag which signals that an interrupt is pending.
+///
+/// Each chip's `Interrupt` enum has a `pending_bits()` method returning these, generated from the
+/// chip's vendor description.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+pub struct PendingBits {
+    /// Address of the register with the flag in the data space.
+    pub register: u16,
+    /// Mask of the flag.  Some interrupts have several, like their enable bits.
+    pub mask: u8,
+    /// Mask of all interrupt flags in the register, which are cleared by writing one.
+    pub flags: u8,
+}
+
+impl PendingBits {
+    /// Returns whether any of the flags is set.
+    ///
+    /// # Safety
+    ///
+    /// - The flag must belong to the chip the program runs on.
+    #[inline]
+    pub unsafe fn is_pending(self) -> bool {
+        crate::register(self.register).read_volatile() & self.mask != 0
+    }
+
+    /// Clears the flag by writing one to it, without clearing the other flags in the register.
+    ///
+    /// Flags which are only cleared by the hardware stay set, like the receive complete flag of a
+    /// USART, which is cleared by reading the received data.
+    ///
+    /// # Safety
+    ///
+    /// - The flag must belong to the chip the program runs on.
+    #[inline]
+    pub unsafe fn clear(self) {
+        let register = crate::register(self.register);
+        free(|_| register.write_volatile((register.read_volatile() & !self.flags) | self.mask))
+    }
+}
+
+/// Description of an interrupt vector.
+///
+/// Each chip's module has a `VECTORS` table containing one entry per interrupt, generated from the
+/// chip's vendor description.  The entry for a specific interrupt can be retrieved with
+/// `Interrupt::info()`:
+///
+/// ```ignore
+/// use avr_device::atmega328p::Interrupt;
+///
+/// let info = Interrupt::TIMER0_OVF.info();
+/// assert_eq!(info.vector, Interrupt::TIMER0_OVF.vector_number());
+/// assert_eq!(Interrupt::from_vector(info.vector), Some(Interrupt::TIMER0_OVF));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+pub struct VectorInfo {
+    /// Name of the interrupt, as used with [`#[avr_device::interrupt]`](macro@crate::interrupt).
+    pub name: &'static str,
+    /// Vector number, i.e. the index of the interrupt in the vector table.
+    pub vector: u8,
+    /// Name of the peripheral which raises the interrupt.
+    pub peripheral: &'static str,
+    /// Description of the interrupt.
+    pub description: &'static str,
+}
+
 #[cfg(feature = "critical-section-impl")]
 mod cs {
     use critical_section::RawRestoreState;
@@ -0,0 +1,359 @@
+use core::cell::UnsafeCell;
+use core::mem::MaybeUninit;
+#[cfg(target_arch = "avr")]
+use core::sync::atomic::compiler_fence;
+use core::sync::atomic::{AtomicU8, Ordering};
+
+/// Lock-free single-producer single-consumer queue.
+///
+/// The queue is [split](Queue::split) into a [`Producer`] and a [`Consumer`], which can be used
+/// in different contexts without a critical section, e.g. to pass received bytes from an interrupt
+/// handler to the main loop.  Both halves only share two single-byte indices, which are read and
+/// written with a single instruction each.
+///
+/// The queue holds up to `N - 1` values.  `N` must be between 2 and 256.
+///
+/// ```ignore
+/// use avr_device::interrupt::{self, Mutex, Producer, Queue};
+/// use core::cell::Cell;
+///
+/// static RX_PRODUCER: Mutex<Cell<Option<Producer<'static, u8, 32>>>> =
+///     Mutex::new(Cell::new(None));
+///
+/// #[avr_device::entry]
+/// fn main() -> ! {
+///     static mut RX_QUEUE: Queue<u8, 32> = Queue::new();
+///     let (producer, mut consumer) = RX_QUEUE.split();
+///     interrupt::free(|cs| RX_PRODUCER.borrow(cs).set(Some(producer)));
+///     unsafe { interrupt::enable() };
+///
+///     loop {
+///         if let Some(byte) = consumer.dequeue() {
+///             // ...
+///         }
+///     }
+/// }
+///
+/// #[avr_device::interrupt(atmega328p)]
+/// fn USART_RX() {
+///     static mut PRODUCER: Option<Producer<'static, u8, 32>> = None;
+///     let producer = PRODUCER.get_or_insert_with(|| {
+///         interrupt::free(|cs| RX_PRODUCER.borrow(cs).take()).unwrap()
+///     });
+///     // Drops the byte if the queue is full.
+///     let _ = producer.enqueue(read_byte());
+/// }
+/// ```
+pub struct Queue<T, const N: usize> {
+    // Index of the next value to dequeue, only written by the consumer.
+    head: Index,
+    // Index of the next value to enqueue, only written by the producer.
+    tail: Index,
+    buffer: [UnsafeCell<MaybeUninit<T>>; N],
+}
+
+impl<T, const N: usize> Queue<T, N> {
+    /// Creates an empty queue.
+    ///
+    /// Panics if `N` isn't between 2 and 256.
+    #[allow(clippy::new_without_default)]
+    pub const fn new() -> Self {
+        assert!(
+            N >= 2 && N <= 256,
+            "The size of a queue must be between 2 and 256"
+        );
+        Self {
+            head: Index::new(),
+            tail: Index::new(),
+            buffer: [const { UnsafeCell::new(MaybeUninit::uninit()) }; N],
+        }
+    }
+
+    /// Returns the maximum number of values in the queue.
+    #[inline]
+    pub const fn capacity(&self) -> usize {
+        N - 1
+    }
+
+    /// Splits the queue into its producer and consumer halves.
+    #[inline]
+    pub fn split(&mut self) -> (Producer<'_, T, N>, Consumer<'_, T, N>) {
+        (Producer { queue: self }, Consumer { queue: self })
+    }
+
+    // Number of values between `head` and `tail`.
+    #[inline(always)]
+    fn len(&self) -> usize {
+        (self.tail.load() as usize + N - self.head.load() as usize) % N
+    }
+}
+
+impl<T, const N: usize> Drop for Queue<T, N> {
+    fn drop(&mut self) {
+        let (_, mut consumer) = self.split();
+        while consumer.dequeue().is_some() {}
+    }
+}
+
+/// Half of a [`Queue`] which enqueues values.
+pub struct Producer<'a, T, const N: usize> {
+    queue: &'a Queue<T, N>,
+}
+
+// SAFETY: The producer only writes to the slots which the consumer doesn't access.
+unsafe impl<T: Send, const N: usize> Send for Producer<'_, T, N> {}
+
+impl<T, const N: usize> Producer<'_, T, N> {
+    /// Adds `value` to the end of the queue.
+    ///
+    /// Returns `value` back if the queue is full.
+    #[inline]
+    pub fn enqueue(&mut self, value: T) -> Result<(), T> {
+        let tail = self.queue.tail.load();
+        let next = next::<N>(tail);
+        if next == self.queue.head.load() {
+            return Err(value);
+        }
+        // SAFETY: The consumer doesn't access the slot at `tail` before `tail` is advanced.
+        unsafe { (*self.queue.buffer[tail as usize].get()).write(value) };
+        self.queue.tail.store(next);
+        Ok(())
+    }
+
+    /// Returns whether the queue is full.
+    #[inline]
+    pub fn is_full(&self) -> bool {
+        self.len() == self.capacity()
+    }
+
+    /// Returns whether the queue is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of values in the queue.
+    ///
+    /// The consumer may dequeue values concurrently, so this is an upper bound.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Returns the maximum number of values in the queue.
+    #[inline]
+    pub const fn capacity(&self) -> usize {
+        N - 1
+    }
+}
+
+/// Half of a [`Queue`] which dequeues values.
+pub struct Consumer<'a, T, const N: usize> {
+    queue: &'a Queue<T, N>,
+}
+
+// SAFETY: The consumer only reads from the slots which the producer doesn't access.
+unsafe impl<T: Send, const N: usize> Send for Consumer<'_, T, N> {}
+
+impl<T, const N: usize> Consumer<'_, T, N> {
+    /// Removes the value at the front of the queue and returns it, if there is one.
+    #[inline]
+    pub fn dequeue(&mut self) -> Option<T> {
+        let head = self.queue.head.load();
+        if head == self.queue.tail.load() {
+            return None;
+        }
+        // SAFETY: The producer initialized the slot at `head` before advancing `tail` past it,
+        // and doesn't access it before `head` is advanced.
+        let value = unsafe { (*self.queue.buffer[head as usize].get()).assume_init_read() };
+        self.queue.head.store(next::<N>(head));
+        Some(value)
+    }
+
+    /// Returns the value at the front of the queue without removing it, if there is one.
+    #[inline]
+    pub fn peek(&self) -> Option<&T> {
+        let head = self.queue.head.load();
+        if head == self.queue.tail.load() {
+            return None;
+        }
+        // SAFETY: As in `dequeue()`.  The slot isn't released while it is borrowed, because
+        // dequeuing borrows the consumer mutably.
+        Some(unsafe { (*self.queue.buffer[head as usize].get()).assume_init_ref() })
+    }
+
+    /// Returns whether the queue is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of values in the queue.
+    ///
+    /// The producer may enqueue values concurrently, so this is a lower bound.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Returns the maximum number of values in the queue.
+    #[inline]
+    pub const fn capacity(&self) -> usize {
+        N - 1
+    }
+}
+
+// Index into the buffer of a queue, written by one half and read by the other.
+struct Index(AtomicU8);
+
+impl Index {
+    const fn new() -> Self {
+        Index(AtomicU8::new(0))
+    }
+
+    #[inline(always)]
+    fn load(&self) -> u8 {
+        cfg_if::cfg_if! {
+            if #[cfg(target_arch = "avr")] {
+                // Atomic loads disable interrupts on AVR, but a single byte is loaded by a single
+                // instruction anyway.
+                // SAFETY: The pointer is valid.
+                let index = unsafe { self.0.as_ptr().read_volatile() };
+                compiler_fence(Ordering::Acquire);
+                index
+            } else {
+                self.0.load(Ordering::Acquire)
+            }
+        }
+    }
+
+    #[inline(always)]
+    fn store(&self, index: u8) {
+        cfg_if::cfg_if! {
+            if #[cfg(target_arch = "avr")] {
+                compiler_fence(Ordering::Release);
+                // SAFETY: The pointer is valid and only one half of the queue stores the index.
+                unsafe { self.0.as_ptr().write_volatile(index) };
+            } else {
+                self.0.store(index, Ordering::Release)
+            }
+        }
+    }
+}
+
+// Returns the index after `index`, wrapping around at `N`.
+#[inline(always)]
+fn next<const N: usize>(index: u8) -> u8 {
+    match index as usize + 1 {
+        next if next == N => 0,
+        next => next as u8,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::cell::Cell;
+
+    // Counts how often it is dropped.
+    struct Counted<'a>(&'a Cell<usize>);
+
+    impl Drop for Counted<'_> {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    #[test]
+    fn full_and_empty_with_two_slots() {
+        let mut queue = Queue::<u32, 2>::new();
+        let (mut producer, mut consumer) = queue.split();
+        assert_eq!(producer.capacity(), 1);
+
+        // The indices wrap around after every value.
+        for value in 0..5 {
+            assert!(producer.is_empty() && consumer.is_empty());
+            assert_eq!(consumer.dequeue(), None);
+            assert_eq!(producer.enqueue(value), Ok(()));
+            assert!(producer.is_full());
+            assert_eq!(producer.enqueue(100), Err(100));
+            assert_eq!((producer.len(), consumer.len()), (1, 1));
+            assert_eq!(consumer.dequeue(), Some(value));
+        }
+        assert!(producer.is_empty() && consumer.is_empty());
+    }
+
+    #[test]
+    fn full_and_empty_with_256_slots() {
+        let mut queue = Queue::<u32, 256>::new();
+        let (mut producer, mut consumer) = queue.split();
+        assert_eq!(producer.capacity(), 255);
+
+        // Each round moves the indices by 100 slots, so they wrap around at different places.
+        let mut next = 0;
+        for _ in 0..10 {
+            for value in next..next + 255 {
+                assert_eq!(producer.enqueue(value), Ok(()));
+            }
+            assert!(producer.is_full());
+            assert_eq!(consumer.len(), 255);
+            assert_eq!(producer.enqueue(1000), Err(1000));
+
+            for value in next..next + 100 {
+                assert_eq!(consumer.dequeue(), Some(value));
+            }
+            assert_eq!(producer.len(), 155);
+            for value in next + 100..next + 255 {
+                assert_eq!(consumer.dequeue(), Some(value));
+            }
+            assert!(consumer.is_empty());
+            assert_eq!(consumer.dequeue(), None);
+
+            for value in 0..100 {
+                assert_eq!(producer.enqueue(value), Ok(()));
+                assert_eq!(consumer.dequeue(), Some(value));
+            }
+            next += 255;
+        }
+    }
+
+    #[test]
+    fn peek_keeps_the_value() {
+        let mut queue = Queue::<u32, 4>::new();
+        let (mut producer, mut consumer) = queue.split();
+        assert_eq!(consumer.peek(), None);
+
+        producer.enqueue(1).unwrap();
+        producer.enqueue(2).unwrap();
+        assert_eq!(consumer.peek(), Some(&1));
+        assert_eq!(consumer.peek(), Some(&1));
+        assert_eq!(consumer.len(), 2);
+
+        assert_eq!(consumer.dequeue(), Some(1));
+        assert_eq!(consumer.peek(), Some(&2));
+        assert_eq!(consumer.dequeue(), Some(2));
+        assert_eq!(consumer.peek(), None);
+    }
+
+    #[test]
+    fn drop_drops_the_remaining_values() {
+        let drops = Cell::new(0);
+        let mut queue = Queue::<Counted<'_>, 4>::new();
+        let (mut producer, mut consumer) = queue.split();
+
+        for _ in 0..3 {
+            assert!(producer.enqueue(Counted(&drops)).is_ok());
+        }
+        // A rejected value is given back and dropped by the caller.
+        drop(producer.enqueue(Counted(&drops)).err());
+        assert_eq!(drops.get(), 1);
+
+        drop(consumer.dequeue());
+        assert_eq!(drops.get(), 2);
+        let _ = consumer.peek();
+        assert_eq!(drops.get(), 2);
+
+        drop(queue);
+        assert_eq!(drops.get(), 4);
+    }
+}
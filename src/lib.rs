@@ -177,12 +177,46 @@
 //!   For a full list of supported devices, see the list at the beginning of this documentation.
 //! * To enable the crate's runtime environment, use the `rt` feature.
 //! * To enable `ufmt` formatting (e.g. `uDebug`), use the `ufmt` feature.
+//! * To run code using the peripherals or the `interrupt` and `asm` modules in unit tests on the
+//!   host, use the `host-mock` feature, see the `mock` module.
 
 #![no_std]
 #![cfg_attr(target_arch = "avr", feature(asm_experimental_arch))] // for experimental AVR asm! macro.
 
 pub mod asm;
+pub mod atomic;
+pub mod ccp;
+pub mod cpuint;
+pub mod eeprom;
+pub mod flash;
+pub mod fuses;
 pub mod interrupt;
+#[cfg(all(feature = "host-mock", not(target_arch = "avr")))]
+pub mod mock;
+pub mod power;
+pub mod progmem;
+pub mod reset;
+pub mod signature;
+pub mod watchdog;
+
+// Returns a pointer to the register at `address` in the data space.  With the `host-mock`
+// feature, the register is in the simulated register file of the selected MCU instead.
+#[inline(always)]
+pub(crate) fn register(address: u16) -> *mut u8 {
+    cfg_if::cfg_if! {
+        if #[cfg(target_arch = "avr")] {
+            address as *mut u8
+        } else if #[cfg(all(feature = "host-mock", avr_device_single_mcu))] {
+            crate::selected::REGISTER_FILE.ptr::<u8>(address).cast_mut()
+        } else if #[cfg(feature = "host-mock")] {
+            let _ = address;
+            panic!("Registers are only simulated with a single selected MCU")
+        } else {
+            let _ = address;
+            unimplemented!()
+        }
+    }
+}
 
 pub(crate) use generic::*;
 #[doc = r"Common register and bit access and modify traits"]
@@ -193,23 +227,114 @@ pub mod generic {
 /// Attribute to declare an interrupt service routine
 ///
 /// ```
+/// #[avr_device::interrupt]
+/// fn INT6() {
+///     // ...
+/// }
+/// ```
+///
+/// When more than one chip feature is enabled, the chip-name must be passed to
+/// the attribute so the interrupt can be mapped to the correct vector:
+///
+/// ```
 /// #[avr_device::interrupt(atmega32u4)]
 /// fn INT6() {
 ///     // ...
 /// }
 /// ```
 ///
+/// Normally, an interrupt handler runs with interrupts disabled.  With the
+/// `nonblocking` option, interrupts are re-enabled as the first instruction of
+/// the handler so that long-running handlers can be preempted by other
+/// interrupts:
+///
+/// ```
+/// #[avr_device::interrupt(atmega32u4, nonblocking)]
+/// fn INT6() {
+///     // Other interrupts can be serviced while this handler runs.
+/// }
+/// ```
+///
+/// A single handler can also serve multiple vectors.  In this case, the vectors
+/// are listed after the chip-name and the name of the function is arbitrary.
+/// The first vector is bound to the handler itself, all others jump to it:
+///
+/// ```ignore
+/// #![feature(asm_experimental_arch)]
+///
+/// #[avr_device::interrupt(atmega2560, PCINT0, PCINT1, PCINT2)]
+/// fn pin_change() {
+///     // ...
+/// }
+/// ```
+///
+/// Interrupts which only need to wake the MCU from sleep don't need any code.
+/// With the `empty` option, the vector consists of a lone `reti` instruction,
+/// without any register saving or trampoline.  The body of the function must
+/// be empty:
+///
+/// ```ignore
+/// #![feature(asm_experimental_arch)]
+///
+/// #[avr_device::interrupt(atmega328p, empty)]
+/// fn WDT() {}
+/// ```
+///
+/// On chips with a CPU interrupt controller, one interrupt can be raised to
+/// level 1, so its handler preempts all other handlers.  Its handler must be
+/// declared with the `level1` option, see the `cpuint` module:
+///
+/// ```ignore
+/// #[avr_device::interrupt(atmega4809, level1)]
+/// fn TCB0_INT() {
+///     // ...
+/// }
+/// ```
+///
 /// # Constraints
-/// - The name of the function must be the name of an interrupt.  Each chip's
-///   module has a `Interrupt` enum defining the available names.
-/// - The attribute needs the chip-name to correctly map the interrupt to its
-///   vector.  This is an unfortunate requirement of the current crate
-///   architecture and might change in the future.
+/// - The name of the function must be the name of an interrupt, unless the
+///   interrupts are listed explicitly.  Each chip's module has a `Interrupt`
+///   enum defining the available names.
+/// - Serving multiple vectors with one handler and `empty` handlers require
+///   `#![feature(asm_experimental_arch)]` in the crate defining the handler.
+/// - `empty` cannot be combined with `nonblocking`.
+/// - The chip-name argument is optional if exactly one chip feature is
+///   enabled.  Otherwise it is required.
+/// - `nonblocking` handlers cannot use `static mut` resources:  The handler
+///   might be preempted by another invocation of itself, so exclusive access
+///   cannot be guaranteed.
+/// - `level1` handlers which serve multiple vectors cannot use `static mut`
+///   resources:  The handler might be preempted by its own level 1 invocation.
 /// - The function must have a signature of `[unsafe] fn() [-> !]`.
 /// - This macro requires the avr-device `rt` crate feature.
 #[cfg(feature = "rt")]
 pub use avr_device_macros::interrupt;
 
+/// Attribute to declare the handler for all interrupts which have no dedicated handler
+///
+/// Without a default handler, an unexpected interrupt makes the program jump to the reset
+/// vector, which silently restarts the firmware.
+///
+/// ```
+/// #[avr_device::default_handler]
+/// fn default_handler() -> ! {
+///     // Log the problem and halt safely...
+///     loop {}
+/// }
+/// ```
+///
+/// At most one default handler may be declared in the entire dependency tree.
+///
+/// # Constraints
+/// - The function must have a signature of `[unsafe] fn() [-> !]`.
+/// - The number of the vector which triggered the default handler is not available:  The
+///   startup code routes all unused vectors through a single jump to the default handler.
+/// - If the handler returns, the interrupt which triggered it will usually fire again
+///   immediately, unless the handler disabled its source.
+/// - This macro requires the avr-device `rt` crate feature.
+#[cfg(feature = "rt")]
+pub use avr_device_macros::default_handler;
+
 /// Attribute to declare the entry point of the program
 ///
 /// Exactly one entry point must be declared in the entire dependency tree.
@@ -229,6 +354,36 @@ pub use avr_device_macros::interrupt;
 #[cfg(feature = "rt")]
 pub use avr_device_macros::entry;
 
+/// Attribute to declare a function which is executed before RAM is initialized
+///
+/// The function is called from the `.init3` section of the startup code.  At this point, the
+/// stack is set up, but `.data` and `.bss` are not initialized yet.  This is the right place to
+/// disable the watchdog after a watchdog reset, to capture reset flags before anything clears
+/// them, or to enable external memory.
+///
+/// ```ignore
+/// #![feature(asm_experimental_arch)]
+///
+/// #[avr_device::pre_init]
+/// unsafe fn before_main() {
+///     // Runs before .data and .bss are initialized.
+/// }
+/// ```
+///
+/// At most one pre-init function may be declared in the entire dependency tree.
+///
+/// # Safety
+/// The function must not access any `static` variables: They are not initialized yet and any
+/// value written to them is overwritten once RAM initialization runs.
+///
+/// # Constraints
+/// - The function must have a signature of `unsafe fn()`.
+/// - `static mut` resources are not available.
+/// - The crate using this attribute must enable `#![feature(asm_experimental_arch)]`.
+/// - This macro requires the avr-device `rt` crate feature.
+#[cfg(feature = "rt")]
+pub use avr_device_macros::pre_init;
+
 #[cfg(not(feature = "device-selected"))]
 compile_error!(
     "This crate requires you to specify your target chip as a feature.
@@ -290,6 +445,7 @@ compile_error!(
 mod devices;
 
 include!(concat!(env!("OUT_DIR"), "/pac/vector.rs"));
+include!(concat!(env!("OUT_DIR"), "/pac/selected.rs"));
 
 #[cfg(feature = "at90usb1286")]
 pub use crate::devices::at90usb1286;
@@ -0,0 +1,465 @@
+//! Host Mock
+//!
+//! With the `host-mock` feature, the functions of [`interrupt`](crate::interrupt) and
+//! [`asm`](crate::asm) work when not compiling for AVR, so code using them can be unit tested on
+//! the development machine.  Instead of executing instructions, they update a simulated CPU state
+//! which tests can inspect:
+//!
+//! ```ignore
+//! use avr_device::mock::{self, Event};
+//!
+//! #[test]
+//! fn feeds_the_watchdog() {
+//!     mock::reset();
+//!     my_driver::poll();
+//!     assert!(!mock::interrupts_enabled());
+//!     assert_eq!(mock::events(), [Event::WatchdogReset]);
+//!     assert!(mock::cycles() >= 16_000);
+//! }
+//! ```
+//!
+//! - The global interrupt flag is a real flag, which [`interrupt::free()`](crate::interrupt::free)
+//!   and friends clear and restore.  As after a reset, interrupts start disabled.
+//! - Delays advance a virtual cycle counter by the number of cycles they would take.
+//! - `nop`, `sleep` and `wdr` are recorded as [`Event`]s.
+//!
+//! Like on the real CPU, there is only one interrupt flag, which all threads share.  While one
+//! thread has interrupts disabled, other threads wait before accessing the flag, so critical
+//! sections exclude each other even across threads.  A thread keeps interrupts disabled until it
+//! enables them again, leaves the outermost critical section or ends.  Tests which check the
+//! flag shouldn't run in parallel with tests which enable interrupts.
+//!
+//! The cycle counter and the events are kept per thread, so tests running in parallel don't
+//! interfere with each other.
+//!
+//! # Registers
+//!
+//! The peripherals of each chip module point into the chip's `REGISTER_FILE`, a [`RegisterFile`]
+//! as large as the chip's data space, instead of the fixed I/O addresses.  Drivers can then be
+//! tested against the generated register API:
+//!
+//! ```ignore
+//! use avr_device::atmega328p::{Peripherals, REGISTER_FILE};
+//!
+//! #[test]
+//! fn starts_the_timer() {
+//!     let mut registers = REGISTER_FILE.lock();
+//!     registers.reset();
+//!     registers.set(0x35, 0x01); // TIFR0.TOV0
+//!
+//!     let dp = unsafe { Peripherals::steal() };
+//!     my_driver::start_timer(dp.TC0);
+//!
+//!     assert_eq!(registers.changes(), [(0x45, 0x05)]); // TCCR0B.CS0 = 5
+//! }
+//! ```
+//!
+//! The register file is shared between all threads, so tests using it are serialized by
+//! [`RegisterFile::lock()`].  It behaves like plain memory: reading a register returns the value
+//! last written or set, and flags aren't cleared by writing one.
+//!
+//! With a single selected MCU, [`interrupt::mask()`](crate::interrupt::mask), the methods of each
+//! chip's `Interrupt` enum which access enable bits and flags, and the [`ccp`](crate::ccp),
+//! [`cpuint`](crate::cpuint), [`eeprom`](crate::eeprom), [`flash`](crate::flash),
+//! [`power`](crate::power) and [`watchdog`](crate::watchdog) modules use the register file as
+//! well.  Timed sequences are written in order, and EEPROM and flash programming finish at once,
+//! clearing the busy flags and commands like the hardware does.  The EEPROM and flash contents
+//! aren't simulated, except where they are mapped into the data space.  Sleeping doesn't wait for
+//! an interrupt, it only enables interrupts and records [`Event::Sleep`].
+//!
+//! This module only exists when not compiling for AVR.
+
+extern crate std;
+
+use core::cell::{Cell, RefCell, UnsafeCell};
+use std::sync::{Condvar, Mutex, MutexGuard, PoisonError};
+use std::thread::{self, ThreadId};
+use std::vec::Vec;
+
+/// An instruction recorded by the mock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    /// [`asm::nop()`](crate::asm::nop) was called.
+    Nop,
+    /// [`asm::sleep()`](crate::asm::sleep) was called.
+    Sleep,
+    /// [`asm::wdr()`](crate::asm::wdr) was called.
+    WatchdogReset,
+}
+
+// The simulated global interrupt flag, shared by all threads.
+struct Interrupts {
+    enabled: bool,
+    // Thread which disabled interrupts, other threads wait until it releases the flag.
+    owner: Option<ThreadId>,
+    // Number of `disable_save()` calls of the owner which weren't restored yet.
+    saved: usize,
+    // Whether the owner called `disable()`, which holds until interrupts are enabled.
+    disabled: bool,
+}
+
+static INTERRUPTS: Mutex<Interrupts> = Mutex::new(Interrupts {
+    enabled: false,
+    owner: None,
+    saved: 0,
+    disabled: false,
+});
+static INTERRUPTS_RELEASED: Condvar = Condvar::new();
+
+// Releases the interrupt flag if the thread owning it ends.
+struct Owner(ThreadId);
+
+impl Drop for Owner {
+    fn drop(&mut self) {
+        let mut interrupts = INTERRUPTS.lock().unwrap_or_else(PoisonError::into_inner);
+        if interrupts.owner == Some(self.0) {
+            interrupts.saved = 0;
+            interrupts.disabled = false;
+            interrupts.release();
+        }
+    }
+}
+
+impl Interrupts {
+    // Waits until no other thread has interrupts disabled and locks the flag for the current
+    // thread.
+    fn acquire() -> (MutexGuard<'static, Interrupts>, ThreadId) {
+        let thread = OWNER.with(|owner| owner.0);
+        let mut interrupts = INTERRUPTS.lock().unwrap_or_else(PoisonError::into_inner);
+        while interrupts.owner.is_some_and(|owner| owner != thread) {
+            interrupts = INTERRUPTS_RELEASED
+                .wait(interrupts)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+        (interrupts, thread)
+    }
+
+    // Lets other threads access the flag, unless the owner still has interrupts disabled.
+    fn release(&mut self) {
+        if self.saved == 0 && !self.disabled {
+            self.owner = None;
+            INTERRUPTS_RELEASED.notify_all();
+        }
+    }
+}
+
+std::thread_local! {
+    static OWNER: Owner = Owner(thread::current().id());
+    static CYCLES: Cell<u64> = const { Cell::new(0) };
+    static EVENTS: RefCell<Vec<Event>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Resets the simulated CPU state: interrupts are disabled, and the cycle counter of the current
+/// thread is zero and no events are recorded for it.
+///
+/// Waits until no other thread has interrupts disabled.
+pub fn reset() {
+    let (mut interrupts, _) = Interrupts::acquire();
+    interrupts.enabled = false;
+    interrupts.saved = 0;
+    interrupts.disabled = false;
+    interrupts.release();
+    CYCLES.set(0);
+    EVENTS.with_borrow_mut(Vec::clear);
+}
+
+/// Returns whether the simulated global interrupt flag is set.
+///
+/// Waits until no other thread has interrupts disabled.
+pub fn interrupts_enabled() -> bool {
+    Interrupts::acquire().0.enabled
+}
+
+/// Sets or clears the simulated global interrupt flag, e.g. to simulate running in an interrupt
+/// handler.
+///
+/// Waits until no other thread has interrupts disabled.  After clearing the flag, other threads
+/// wait until the current thread sets it again or ends.
+pub fn set_interrupts_enabled(enabled: bool) {
+    match enabled {
+        true => enable(),
+        false => disable(),
+    }
+}
+
+/// Returns the number of CPU cycles spent in delays so far.
+pub fn cycles() -> u64 {
+    CYCLES.get()
+}
+
+/// Returns the events recorded so far.
+pub fn events() -> Vec<Event> {
+    EVENTS.with_borrow(Vec::clone)
+}
+
+/// Returns the events recorded so far and clears them.
+pub fn take_events() -> Vec<Event> {
+    EVENTS.take()
+}
+
+/// Simulated registers of a chip, `N` bytes of its data space.
+///
+/// Each chip's module has a `REGISTER_FILE` static of this type.
+pub struct RegisterFile<const N: usize> {
+    registers: UnsafeCell<[u8; N]>,
+    // Register values as set by the test, to find the ones changed by the code under test.
+    expected: UnsafeCell<[u8; N]>,
+    lock: Mutex<()>,
+}
+
+// SAFETY: Apart from the peripherals, the registers are only accessed while holding the lock.
+unsafe impl<const N: usize> Sync for RegisterFile<N> {}
+
+impl<const N: usize> RegisterFile<N> {
+    /// Creates a register file with all registers zero.
+    #[allow(clippy::new_without_default)]
+    pub const fn new() -> Self {
+        Self {
+            registers: UnsafeCell::new([0; N]),
+            expected: UnsafeCell::new([0; N]),
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// Returns a pointer to the register at `address` in the data space.
+    ///
+    /// This is the address of the generated peripherals.
+    pub const fn ptr<T>(&'static self, address: u16) -> *const T {
+        byte(&self.registers, address).cast_const().cast()
+    }
+
+    /// Waits until no other thread uses the register file and returns a handle for setting and
+    /// inspecting the registers.
+    ///
+    /// Tests should hold the handle while running code which accesses the peripherals.
+    pub fn lock(&self) -> Registers<'_, N> {
+        Registers {
+            file: self,
+            _guard: self
+                .lock
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner()),
+        }
+    }
+}
+
+/// Exclusive access to a [`RegisterFile`], returned by [`RegisterFile::lock()`].
+pub struct Registers<'a, const N: usize> {
+    file: &'a RegisterFile<N>,
+    _guard: MutexGuard<'a, ()>,
+}
+
+impl<const N: usize> Registers<'_, N> {
+    /// Sets all registers to zero.
+    pub fn reset(&mut self) {
+        for address in 0..N {
+            self.set(address as u16, 0);
+        }
+    }
+
+    /// Sets the register at `address`, e.g. to simulate a flag set by the hardware.
+    ///
+    /// Panics if `address` is outside of the data space.
+    pub fn set(&mut self, address: u16, value: u8) {
+        // SAFETY: The lock is held.
+        unsafe {
+            byte(&self.file.registers, address).write_volatile(value);
+            byte(&self.file.expected, address).write(value);
+        }
+    }
+
+    /// Returns the value of the register at `address`.
+    ///
+    /// Panics if `address` is outside of the data space.
+    pub fn get(&self, address: u16) -> u8 {
+        // SAFETY: The lock is held.
+        unsafe { byte(&self.file.registers, address).read_volatile() }
+    }
+
+    /// Returns the addresses and values of all registers which differ from the value they were
+    /// last [`set()`](Registers::set) or [`reset()`](Registers::reset) to, i.e. the writes of the
+    /// code under test.
+    pub fn changes(&self) -> Vec<(u16, u8)> {
+        (0..N)
+            .map(|address| address as u16)
+            .filter_map(|address| {
+                let value = self.get(address);
+                // SAFETY: The lock is held.
+                let expected = unsafe { byte(&self.file.expected, address).read() };
+                (value != expected).then_some((address, value))
+            })
+            .collect()
+    }
+}
+
+// Returns a pointer to the byte at `address` of `memory`, panics if it is out of bounds.
+const fn byte<const N: usize>(memory: &UnsafeCell<[u8; N]>, address: u16) -> *mut u8 {
+    assert!(
+        (address as usize) < N,
+        "Address outside of the register file"
+    );
+    // SAFETY: The address is within the array.
+    unsafe { memory.get().cast::<u8>().add(address as usize) }
+}
+
+pub(crate) fn disable() {
+    let (mut interrupts, thread) = Interrupts::acquire();
+    interrupts.enabled = false;
+    interrupts.owner = Some(thread);
+    interrupts.disabled = true;
+}
+
+pub(crate) fn enable() {
+    let (mut interrupts, _) = Interrupts::acquire();
+    interrupts.enabled = true;
+    interrupts.disabled = false;
+    interrupts.release();
+}
+
+// Clears the flag and returns its previous state in one step, so no other thread can change it in
+// between.
+pub(crate) fn disable_save() -> bool {
+    let (mut interrupts, thread) = Interrupts::acquire();
+    let enabled = core::mem::replace(&mut interrupts.enabled, false);
+    interrupts.owner = Some(thread);
+    interrupts.saved += 1;
+    enabled
+}
+
+pub(crate) fn restore(enabled: bool) {
+    let (mut interrupts, _) = Interrupts::acquire();
+    interrupts.enabled = enabled;
+    interrupts.saved = interrupts.saved.saturating_sub(1);
+    if enabled {
+        interrupts.disabled = false;
+    }
+    interrupts.release();
+}
+
+pub(crate) fn advance(cycles: u32) {
+    CYCLES.set(CYCLES.get() + cycles as u64);
+}
+
+pub(crate) fn record(event: Event) {
+    EVENTS.with_borrow_mut(|events| events.push(event));
+}
+
+// Serializes the tests which check the shared interrupt flag or enable interrupts.
+#[cfg(test)]
+pub(crate) fn lock_flag() -> MutexGuard<'static, ()> {
+    static FLAG: Mutex<()> = Mutex::new(());
+    FLAG.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interrupt::{self, Mutex as InterruptMutex};
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    #[test]
+    fn free_disables_and_restores_interrupts() {
+        let _flag = lock_flag();
+        reset();
+        assert!(!interrupts_enabled());
+
+        unsafe { interrupt::enable() };
+        interrupt::free(|_| {
+            assert!(!interrupt::is_enabled());
+            interrupt::free(|_| assert!(!interrupt::is_enabled()));
+            assert!(!interrupt::is_enabled());
+        });
+        assert!(interrupts_enabled());
+
+        interrupt::disable();
+        interrupt::free(|_| {});
+        assert!(!interrupts_enabled());
+        reset();
+    }
+
+    #[test]
+    fn free_excludes_other_threads() {
+        static COUNTER: InterruptMutex<Cell<u32>> = InterruptMutex::new(Cell::new(0));
+        let _flag = lock_flag();
+
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                thread::spawn(|| {
+                    for _ in 0..10_000 {
+                        interrupt::free(|cs| {
+                            let counter = COUNTER.borrow(cs);
+                            let value = counter.get();
+                            thread::yield_now();
+                            counter.set(value + 1);
+                        });
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        assert_eq!(interrupt::free(|cs| COUNTER.borrow(cs).get()), 40_000);
+    }
+
+    #[test]
+    fn disable_holds_until_enable() {
+        static VALUE: InterruptMutex<Cell<u8>> = InterruptMutex::new(Cell::new(0));
+
+        let _flag = lock_flag();
+        let (disabled, wait) = mpsc::channel();
+        let handle = thread::spawn(move || {
+            interrupt::disable();
+            disabled.send(()).unwrap();
+            thread::sleep(Duration::from_millis(50));
+            // SAFETY: Interrupts are disabled.
+            VALUE
+                .borrow(unsafe { interrupt::CriticalSection::new() })
+                .set(1);
+            unsafe { interrupt::enable() };
+        });
+        wait.recv().unwrap();
+        assert_eq!(interrupt::free(|cs| VALUE.borrow(cs).get()), 1);
+        handle.join().unwrap();
+        reset();
+    }
+
+    #[test]
+    fn ending_thread_releases_interrupts() {
+        let _flag = lock_flag();
+        thread::spawn(interrupt::disable).join().unwrap();
+        assert!(!interrupts_enabled());
+        interrupt::free(|_| {});
+        reset();
+    }
+
+    #[test]
+    fn cycles_and_events_are_per_thread() {
+        crate::asm::nop();
+        advance(10);
+        thread::spawn(|| {
+            crate::asm::wdr();
+            advance(5);
+            assert_eq!(take_events(), [Event::WatchdogReset]);
+            assert_eq!(cycles(), 5);
+        })
+        .join()
+        .unwrap();
+        assert_eq!(events(), [Event::Nop]);
+        assert_eq!(cycles(), 10);
+    }
+
+    #[test]
+    fn register_file_reports_changes() {
+        static FILE: RegisterFile<0x100> = RegisterFile::new();
+
+        let mut registers = FILE.lock();
+        registers.reset();
+        registers.set(0x35, 0x01);
+        // SAFETY: The lock is held.
+        unsafe { FILE.ptr::<u8>(0x45).cast_mut().write_volatile(0x05) };
+        assert_eq!(registers.get(0x35), 0x01);
+        assert_eq!(registers.changes(), [(0x45, 0x05)]);
+    }
+}
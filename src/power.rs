@@ -0,0 +1,294 @@
+//! Sleep Modes
+//!
+//! [`asm::sleep()`](crate::asm::sleep) only executes the `sleep` instruction, which does nothing
+//! unless a sleep mode was selected and sleeping was enabled before.  This module does both, and
+//! enables interrupts right before sleeping, so an interrupt can't slip in between checking for
+//! work and going to sleep:
+//!
+//! ```ignore
+//! use avr_device::interrupt::Mutex;
+//! use avr_device::power::{self, SleepMode};
+//! use core::cell::Cell;
+//!
+//! static RECEIVED: Mutex<Cell<bool>> = Mutex::new(Cell::new(false));
+//!
+//! // Sleeps until an interrupt handler sets `RECEIVED`.
+//! // SAFETY: Not within a critical section.
+//! unsafe { power::sleep_until(SleepMode::PowerDown, |cs| RECEIVED.borrow(cs).get()) };
+//! ```
+//!
+//! The brown-out detector can be turned off during sleep on chips which support this, which saves
+//! power in the deeper sleep modes:
+//!
+//! ```ignore
+//! unsafe { power::Sleep::new(SleepMode::PowerDown).disable_bod(true).sleep() };
+//! ```
+//!
+//! Interrupts are always enabled after sleeping, because only an interrupt can wake the chip.
+//! Like [`interrupt::enable()`](crate::interrupt::enable), the functions which sleep are thus
+//! unsafe to call within a critical section.
+//! Each chip's module has a `SLEEP` constant describing its sleep controller.  The functions of
+//! this module are only available when exactly one MCU feature is selected.
+
+#[cfg(all(target_arch = "avr", avr_device_single_mcu))]
+use core::arch::asm;
+
+/// A sleep mode.
+///
+/// Not all chips support all modes, see the datasheet for which parts of the chip keep running
+/// in each mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+pub enum SleepMode {
+    /// The CPU stops, everything else keeps running.
+    Idle,
+    /// The CPU and I/O clocks stop, to reduce noise for ADC conversions.
+    AdcNoiseReduction,
+    /// All clocks stop, only asynchronous events wake the chip.
+    PowerDown,
+    /// Like [`PowerDown`](SleepMode::PowerDown), but an asynchronous timer keeps running.
+    PowerSave,
+    /// Like [`PowerDown`](SleepMode::PowerDown), but the main oscillator keeps running for a
+    /// faster wake-up.
+    Standby,
+    /// Like [`PowerSave`](SleepMode::PowerSave), but the main oscillator keeps running for a
+    /// faster wake-up.
+    ExtendedStandby,
+}
+
+/// Description of the sleep controller of a chip.
+///
+/// Each chip's module has a `SLEEP` constant of this type, generated from the chip's vendor
+/// description.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SleepController {
+    /// Address of the register with the sleep mode and enable bits (`SMCR`, `MCUCR` or
+    /// `SLPCTRL.CTRLA`) in the data space.
+    pub register: u16,
+    /// Mask of the sleep enable bit (`SE` or `SEN`).
+    pub enable: u8,
+    /// Mask of the sleep mode bits.
+    pub mode_mask: u8,
+    /// Supported sleep modes and their sleep mode bits.
+    pub modes: &'static [(SleepMode, u8)],
+    /// Control of the brown-out detector during sleep, if the chip supports turning it off.
+    pub bod_disable: Option<BodDisable>,
+}
+
+/// Bits which turn off the brown-out detector during sleep.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BodDisable {
+    /// Address of the register with `BODS` and `BODSE` in the data space.
+    pub register: u16,
+    /// Mask of `BODS`.
+    pub bods: u8,
+    /// Mask of `BODSE`.
+    pub bods_enable: u8,
+}
+
+/// Settings for sleeping.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+pub struct Sleep {
+    mode: SleepMode,
+    disable_bod: bool,
+}
+
+impl Sleep {
+    /// Sleeps in `mode`, with the brown-out detector running.
+    #[inline]
+    pub const fn new(mode: SleepMode) -> Self {
+        Self {
+            mode,
+            disable_bod: false,
+        }
+    }
+
+    /// Turns the brown-out detector off during sleep, on chips which support this.
+    ///
+    /// On other chips, the fuses decide whether the brown-out detector runs during sleep.
+    #[inline]
+    pub const fn disable_bod(self, disable_bod: bool) -> Self {
+        Self {
+            disable_bod,
+            ..self
+        }
+    }
+
+    /// Enables interrupts and sleeps until one of them wakes the chip.
+    ///
+    /// Panics if the chip doesn't support the sleep mode.
+    ///
+    /// This method is only available with a single selected MCU.
+    ///
+    /// # Safety
+    ///
+    /// - Do not call this method inside an [crate::interrupt::free] critical section
+    #[cfg(avr_device_single_mcu)]
+    #[inline]
+    pub unsafe fn sleep(self) {
+        crate::interrupt::disable();
+        // SAFETY: Interrupts are disabled.
+        unsafe { self.enter() };
+    }
+
+    /// Sleeps until `condition` returns `true`.
+    ///
+    /// `condition` runs with interrupts disabled.  If it returns `false`, interrupts are enabled
+    /// and the chip goes to sleep without an interrupt handler being able to run in between, so
+    /// a wake-up can't be missed.  When the chip wakes up, `condition` is checked again.
+    ///
+    /// Panics if the chip doesn't support the sleep mode.
+    ///
+    /// This method is only available with a single selected MCU.
+    ///
+    /// # Safety
+    ///
+    /// - Do not call this method inside an [crate::interrupt::free] critical section
+    #[cfg(avr_device_single_mcu)]
+    #[inline]
+    pub unsafe fn sleep_until<F>(self, mut condition: F)
+    where
+        F: FnMut(crate::interrupt::CriticalSection) -> bool,
+    {
+        loop {
+            crate::interrupt::disable();
+            // SAFETY: Interrupts are disabled.
+            if condition(unsafe { crate::interrupt::CriticalSection::new() }) {
+                // SAFETY: The caller ensures that this is not within a critical section.
+                unsafe { crate::interrupt::enable() };
+                return;
+            }
+            // SAFETY: Interrupts are disabled.
+            unsafe { self.enter() };
+        }
+    }
+
+    // Selects the sleep mode and sleeps, interrupts must be disabled.
+    #[cfg(avr_device_single_mcu)]
+    #[inline(always)]
+    unsafe fn enter(self) {
+        let sleep = crate::selected::SLEEP;
+        let Some(&(_, mode)) = sleep.modes.iter().find(|(mode, _)| *mode == self.mode) else {
+            panic!("This chip doesn't support the sleep mode");
+        };
+        let register = crate::register(sleep.register);
+        let value = register.read_volatile() & !sleep.mode_mask;
+        register.write_volatile(value | mode | sleep.enable);
+
+        cfg_if::cfg_if! {
+            if #[cfg(target_arch = "avr")] {
+                // `sei` takes effect after the next instruction, so no interrupt can be serviced
+                // before the chip sleeps.  `BODS` must be set within four cycles after `BODSE`,
+                // and the chip must sleep within three cycles after that.
+                match sleep.bod_disable {
+                    Some(bod) if self.disable_bod => {
+                        let bod_value = (bod.register as *const u8).read_volatile();
+                        asm!(
+                            "st X, {enable}",
+                            "st X, {disable}",
+                            "sei",
+                            "sleep",
+                            enable = in(reg) bod_value | bod.bods | bod.bods_enable,
+                            disable = in(reg) (bod_value | bod.bods) & !bod.bods_enable,
+                            in("X") bod.register,
+                        );
+                    }
+                    _ => asm!("sei", "sleep"),
+                }
+                register.write_volatile(register.read_volatile() & !sleep.enable);
+            } else if #[cfg(feature = "host-mock")] {
+                // The mock doesn't wait for an interrupt, it only records the `sleep`.
+                if let Some(bod) = sleep.bod_disable.filter(|_| self.disable_bod) {
+                    let bod_register = crate::register(bod.register);
+                    let bod_value = bod_register.read_volatile();
+                    bod_register.write_volatile(bod_value | bod.bods | bod.bods_enable);
+                    bod_register.write_volatile((bod_value | bod.bods) & !bod.bods_enable);
+                }
+                crate::interrupt::enable();
+                crate::asm::sleep();
+                register.write_volatile(register.read_volatile() & !sleep.enable);
+            } else {
+                unimplemented!()
+            }
+        }
+    }
+}
+
+/// Enables interrupts and sleeps in `mode` until one of them wakes the chip.
+///
+/// Panics if the chip doesn't support the sleep mode.
+///
+/// This function is only available with a single selected MCU.
+///
+/// # Safety
+///
+/// - Do not call this function inside an [crate::interrupt::free] critical section
+#[cfg(avr_device_single_mcu)]
+#[inline]
+pub unsafe fn sleep(mode: SleepMode) {
+    Sleep::new(mode).sleep()
+}
+
+/// Sleeps in `mode` until `condition` returns `true`, see [`Sleep::sleep_until()`].
+///
+/// This function is only available with a single selected MCU.
+///
+/// # Safety
+///
+/// - Do not call this function inside an [crate::interrupt::free] critical section
+#[cfg(avr_device_single_mcu)]
+#[inline]
+pub unsafe fn sleep_until<F>(mode: SleepMode, condition: F)
+where
+    F: FnMut(crate::interrupt::CriticalSection) -> bool,
+{
+    Sleep::new(mode).sleep_until(condition)
+}
+
+#[cfg(all(test, feature = "host-mock", avr_device_single_mcu))]
+mod tests {
+    use super::*;
+    use crate::mock::{self, Event};
+
+    #[test]
+    fn sleep_selects_the_mode_and_enables_interrupts() {
+        let _flag = mock::lock_flag();
+        let mut registers = crate::selected::REGISTER_FILE.lock();
+        registers.reset();
+        mock::reset();
+
+        let sleep = crate::selected::SLEEP;
+        let &(mode, bits) = sleep.modes.last().unwrap();
+        let mut checks = 0;
+        unsafe {
+            sleep_until(mode, |_| {
+                checks += 1;
+                checks == 2
+            })
+        };
+
+        // The mode stays selected, but sleeping is disabled again.
+        assert_eq!(registers.get(sleep.register), bits);
+        assert!(mock::interrupts_enabled());
+        assert_eq!(mock::take_events(), [Event::Sleep]);
+        mock::reset();
+    }
+
+    #[test]
+    fn sleep_disables_the_bod() {
+        let Some(bod) = crate::selected::SLEEP.bod_disable else {
+            return;
+        };
+        let _flag = mock::lock_flag();
+        let mut registers = crate::selected::REGISTER_FILE.lock();
+        registers.reset();
+        mock::reset();
+
+        let &(mode, _) = crate::selected::SLEEP.modes.last().unwrap();
+        unsafe { Sleep::new(mode).disable_bod(true).sleep() };
+        let bits = bod.bods | bod.bods_enable;
+        assert_eq!(registers.get(bod.register) & bits, bod.bods);
+        mock::reset();
+    }
+}
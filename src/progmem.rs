@@ -0,0 +1,352 @@
+//! Program Memory
+//!
+//! Constant data like lookup tables and strings normally ends up in `.data`, which occupies both
+//! flash and SRAM because it is copied to SRAM at startup.  Data declared with the
+//! [`progmem!`](crate::progmem!) macro stays in flash (the `.progmem.data` section) and is read
+//! with the `lpm` instruction instead:
+//!
+//! ```ignore
+//! avr_device::progmem! {
+//!     static SINE: [u8; 4] = [0, 90, 127, 90];
+//!     static GREETING: str = "Hello from flash!";
+//! }
+//!
+//! let peak = SINE.load_at(2);
+//! for value in &SINE {
+//!     // ...
+//! }
+//! ufmt::uwriteln!(&mut serial, "{}", GREETING).unwrap();
+//! ```
+//!
+//! The default linker script places `.progmem.data` right after the interrupt vectors, so it is
+//! always within the first 64 KiB of flash which `lpm` can reach.  On chips with more flash, like
+//! the ATmega2560 or ATmega1284P, [`read_byte_far()`] reads from anywhere in flash using `elpm`
+//! and `RAMPZ`.
+//!
+//! When not compiling for AVR, program memory is ordinary memory and is read directly.
+
+#[cfg(target_arch = "avr")]
+use core::arch::asm;
+use core::mem::MaybeUninit;
+
+/// Declares statics which are placed in program memory.
+///
+/// Each static of type `T` becomes a [`ProgMem<T>`](ProgMem), a static of type `str` becomes a
+/// [`ProgMemStr`].
+///
+/// ```ignore
+/// avr_device::progmem! {
+///     /// Gamma correction table.
+///     pub static GAMMA: [u8; 8] = [0, 1, 4, 10, 21, 38, 62, 95];
+///     static NAME: str = "avr-device";
+/// }
+/// ```
+#[macro_export]
+macro_rules! progmem {
+    () => {};
+    ($(#[$attr:meta])* $vis:vis static $name:ident: str = $value:expr; $($rest:tt)*) => {
+        $(#[$attr])*
+        #[cfg_attr(target_arch = "avr", link_section = ".progmem.data")]
+        $vis static $name: $crate::progmem::ProgMemStr<{ $value.len() }> =
+            // SAFETY: The static is placed in program memory.
+            unsafe { $crate::progmem::ProgMemStr::new($value) };
+        $crate::progmem! { $($rest)* }
+    };
+    ($(#[$attr:meta])* $vis:vis static $name:ident: $ty:ty = $value:expr; $($rest:tt)*) => {
+        $(#[$attr])*
+        #[cfg_attr(target_arch = "avr", link_section = ".progmem.data")]
+        $vis static $name: $crate::progmem::ProgMem<$ty> =
+            // SAFETY: The static is placed in program memory.
+            unsafe { $crate::progmem::ProgMem::new($value) };
+        $crate::progmem! { $($rest)* }
+    };
+}
+
+/// A value in program memory.
+///
+/// Usually declared with the [`progmem!`](crate::progmem!) macro.  The value can't be accessed
+/// through a reference, only copied out of program memory with [`load()`](ProgMem::load) and the
+/// methods for arrays.
+#[repr(transparent)]
+pub struct ProgMem<T>(T);
+
+impl<T> ProgMem<T> {
+    /// Wraps `value` for placing it in program memory.
+    ///
+    /// # Safety
+    ///
+    /// The result must be the initializer of a static in the `.progmem.data` section, otherwise
+    /// loading it reads whatever is at the same address in flash.
+    #[inline]
+    pub const unsafe fn new(value: T) -> Self {
+        Self(value)
+    }
+
+    /// Returns the address of the value in program memory.
+    #[inline]
+    pub const fn as_ptr(&self) -> *const T {
+        &self.0
+    }
+}
+
+impl<T: Copy> ProgMem<T> {
+    /// Copies the value out of program memory.
+    #[inline]
+    pub fn load(&self) -> T {
+        // SAFETY: The value is in program memory.
+        unsafe { read_value(self.as_ptr()) }
+    }
+}
+
+impl<T: Copy, const N: usize> ProgMem<[T; N]> {
+    /// Returns the number of elements of the array.
+    #[inline]
+    pub const fn len(&self) -> usize {
+        N
+    }
+
+    /// Returns `true` if the array has no elements.
+    #[inline]
+    pub const fn is_empty(&self) -> bool {
+        N == 0
+    }
+
+    /// Copies the element at `index` out of program memory, or returns `None` if `index` is out
+    /// of bounds.
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<T> {
+        match index < N {
+            // SAFETY: The element is within the array in program memory.
+            true => Some(unsafe { read_value(self.as_ptr().cast::<T>().add(index)) }),
+            false => None,
+        }
+    }
+
+    /// Copies the element at `index` out of program memory.
+    ///
+    /// Panics if `index` is out of bounds.
+    #[inline]
+    pub fn load_at(&self, index: usize) -> T {
+        match self.get(index) {
+            Some(element) => element,
+            None => panic!("Index out of bounds"),
+        }
+    }
+
+    /// Returns an iterator which copies the elements out of program memory.
+    #[inline]
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            next: self.as_ptr().cast(),
+            remaining: N,
+            _array: core::marker::PhantomData,
+        }
+    }
+}
+
+impl<'a, T: Copy, const N: usize> IntoIterator for &'a ProgMem<[T; N]> {
+    type Item = T;
+    type IntoIter = Iter<'a, T>;
+
+    #[inline]
+    fn into_iter(self) -> Iter<'a, T> {
+        self.iter()
+    }
+}
+
+/// Iterator over the elements of an array in program memory.
+pub struct Iter<'a, T> {
+    next: *const T,
+    remaining: usize,
+    _array: core::marker::PhantomData<&'a ProgMem<T>>,
+}
+
+impl<T: Copy> Iterator for Iter<'_, T> {
+    type Item = T;
+
+    #[inline]
+    fn next(&mut self) -> Option<T> {
+        if self.remaining == 0 {
+            return None;
+        }
+        // SAFETY: The element is within the array in program memory.
+        let element = unsafe { read_value(self.next) };
+        self.next = self.next.wrapping_add(1);
+        self.remaining -= 1;
+        Some(element)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<T: Copy> ExactSizeIterator for Iter<'_, T> {}
+
+/// A string of `N` bytes in program memory.
+///
+/// Usually declared with the [`progmem!`](crate::progmem!) macro.  Implements `Display` (and
+/// `uDisplay` with the `ufmt` feature), which formats the string in small chunks instead of
+/// copying all of it into SRAM.
+#[repr(transparent)]
+pub struct ProgMemStr<const N: usize>(ProgMem<[u8; N]>);
+
+impl<const N: usize> ProgMemStr<N> {
+    /// Wraps `value` for placing it in program memory.
+    ///
+    /// Panics if `value` isn't `N` bytes long.
+    ///
+    /// # Safety
+    ///
+    /// The result must be the initializer of a static in the `.progmem.data` section, otherwise
+    /// loading it reads whatever is at the same address in flash.
+    pub const unsafe fn new(value: &str) -> Self {
+        let value = value.as_bytes();
+        assert!(value.len() == N, "String length doesn't match");
+        let mut bytes = [0; N];
+        let mut i = 0;
+        while i < N {
+            bytes[i] = value[i];
+            i += 1;
+        }
+        Self(ProgMem::new(bytes))
+    }
+
+    /// Returns the length of the string in bytes.
+    #[inline]
+    pub const fn len(&self) -> usize {
+        N
+    }
+
+    /// Returns `true` if the string is empty.
+    #[inline]
+    pub const fn is_empty(&self) -> bool {
+        N == 0
+    }
+
+    /// Returns an iterator which copies the bytes of the string out of program memory.
+    #[inline]
+    pub fn bytes(&self) -> Iter<'_, u8> {
+        self.0.iter()
+    }
+
+    /// Copies the string into `buffer` and returns it.
+    #[inline]
+    pub fn load<'b>(&self, buffer: &'b mut [u8; N]) -> &'b str {
+        *buffer = self.0.load();
+        // SAFETY: The bytes were a `str` when the static was declared.
+        unsafe { core::str::from_utf8_unchecked(buffer) }
+    }
+
+    /// Calls `write` with consecutive pieces of the string.  Pieces never split a character.
+    pub fn for_each_chunk<E>(&self, mut write: impl FnMut(&str) -> Result<(), E>) -> Result<(), E> {
+        let mut buffer = [0; 16];
+        let mut filled = 0;
+        for byte in self.bytes() {
+            buffer[filled] = byte;
+            filled += 1;
+            if filled == buffer.len() {
+                // An incomplete character at the end is at most 3 bytes long, so some of the
+                // buffer is always written.
+                let valid = match core::str::from_utf8(&buffer) {
+                    Ok(chunk) => chunk.len(),
+                    Err(error) => error.valid_up_to(),
+                };
+                // SAFETY: The bytes up to `valid` were checked above.
+                write(unsafe { core::str::from_utf8_unchecked(&buffer[..valid]) })?;
+                buffer.copy_within(valid.., 0);
+                filled -= valid;
+            }
+        }
+        // SAFETY: The string ends with a complete character.
+        write(unsafe { core::str::from_utf8_unchecked(&buffer[..filled]) })
+    }
+}
+
+impl<const N: usize> core::fmt::Display for ProgMemStr<N> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        self.for_each_chunk(|chunk| f.write_str(chunk))
+    }
+}
+
+#[cfg(feature = "ufmt")]
+impl<const N: usize> ufmt::uDisplay for ProgMemStr<N> {
+    fn fmt<W>(&self, f: &mut ufmt::Formatter<'_, W>) -> Result<(), W::Error>
+    where
+        W: ufmt::uWrite + ?Sized,
+    {
+        self.for_each_chunk(|chunk| f.write_str(chunk))
+    }
+}
+
+/// Reads the byte at `address` in the first 64 KiB of program memory, using `lpm`.
+///
+/// # Safety
+///
+/// `address` must be a valid address in program memory.
+#[inline(always)]
+pub unsafe fn read_byte(address: *const u8) -> u8 {
+    cfg_if::cfg_if! {
+        if #[cfg(target_arch = "avr")] {
+            let byte;
+            asm!(
+                "lpm {byte}, Z",
+                byte = out(reg) byte,
+                in("Z") address,
+                options(pure, readonly, nostack, preserves_flags),
+            );
+            byte
+        } else {
+            address.read()
+        }
+    }
+}
+
+/// Reads the byte at `address` anywhere in program memory, using `elpm`.
+///
+/// `RAMPZ` is restored afterwards and interrupts are disabled while it is changed.  This function
+/// is only available on chips with more than 64 KiB of flash.
+///
+/// # Safety
+///
+/// `address` must be a valid address in program memory.
+#[cfg(any(doc, target_feature = "elpm"))]
+#[inline(always)]
+pub unsafe fn read_byte_far(address: u32) -> u8 {
+    cfg_if::cfg_if! {
+        if #[cfg(target_arch = "avr")] {
+            crate::interrupt::free(|_| {
+                let byte;
+                // `RAMPZ` is at I/O address 0x3b on all chips which have it.
+                asm!(
+                    "in {rampz}, 0x3b",
+                    "out 0x3b, {high}",
+                    "elpm {byte}, Z",
+                    "out 0x3b, {rampz}",
+                    rampz = out(reg) _,
+                    high = in(reg) (address >> 16) as u8,
+                    byte = out(reg) byte,
+                    in("Z") address as u16,
+                    options(readonly, nostack, preserves_flags),
+                );
+                byte
+            })
+        } else {
+            let _ = address;
+            unimplemented!()
+        }
+    }
+}
+
+/// Copies a value of type `T` out of program memory.
+#[inline]
+unsafe fn read_value<T: Copy>(source: *const T) -> T {
+    let mut value = MaybeUninit::<T>::uninit();
+    let source = source.cast::<u8>();
+    let destination = value.as_mut_ptr().cast::<u8>();
+    for i in 0..core::mem::size_of::<T>() {
+        destination.add(i).write(read_byte(source.add(i)));
+    }
+    value.assume_init()
+}
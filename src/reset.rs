@@ -0,0 +1,177 @@
+//! Reset Cause
+//!
+//! Every AVR records why it was reset in a register: `MCUSR` (`MCUCSR` on older chips) on classic
+//! megaAVR and tinyAVR devices and `RSTCTRL.RSTFR` on the newer ones.  The flags stay set until
+//! they are cleared explicitly, so they have to be read and cleared before anything else runs.
+//!
+//! With the `rt` feature enabled, the runtime copies the flags to RAM and clears them before any
+//! other startup code (including [`#[pre_init]`](macro@crate::pre_init)) runs.  They can then be
+//! queried at any time:
+//!
+//! ```ignore
+//! use avr_device::reset::{self, ResetCause};
+//!
+//! match reset::cause() {
+//!     Some(ResetCause::Watchdog) => {
+//!         // Recover from a hang...
+//!     }
+//!     _ => {}
+//! }
+//! ```
+//!
+//! `cause()` and `flags()` are only available when exactly one MCU feature is selected.
+//! Each chip's module has a `RESET_FLAGS` constant describing its reset flag register, which can
+//! be used to decode the flags read by other means with [`ResetFlagRegister::decode`].
+//!
+//! *Note*: Clearing the watchdog reset flag is a prerequisite for disabling the watchdog.  After a
+//! watchdog reset, the watchdog stays enabled and needs to be disabled or serviced early on.
+
+/// Reason for a reset of the MCU.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+pub enum ResetCause {
+    /// The supply voltage rose above the power-on reset threshold.
+    PowerOn,
+    /// The external reset pin was pulled low.
+    External,
+    /// The supply voltage dropped below the brown-out detection level.
+    BrownOut,
+    /// The watchdog timer expired.
+    Watchdog,
+    /// A reset was requested through the JTAG interface.
+    Jtag,
+    /// A reset was requested by software through `RSTCTRL.SWRR`.
+    Software,
+    /// A reset was requested through the UPDI interface.
+    Updi,
+}
+
+impl ResetCause {
+    /// All reset causes, ordered by priority.  If multiple reset flags are set, the first one in
+    /// this list is reported as the cause.  A power-on reset e.g. usually also sets the brown-out
+    /// flag.
+    pub const PRIORITY: [ResetCause; 7] = [
+        ResetCause::PowerOn,
+        ResetCause::BrownOut,
+        ResetCause::External,
+        ResetCause::Watchdog,
+        ResetCause::Jtag,
+        ResetCause::Software,
+        ResetCause::Updi,
+    ];
+}
+
+/// Description of the reset flag register of a chip.
+///
+/// Each chip's module has a `RESET_FLAGS` constant of this type, generated from the chip's vendor
+/// description.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResetFlagRegister {
+    /// Address of the register in the data space.
+    pub address: u16,
+    /// Whether flags are cleared by writing one to them (`RSTFR`) instead of zero (`MCUSR`).
+    pub clear_by_writing_one: bool,
+    // Masks of the flags, indexed by `ResetCause`.  Zero if the chip doesn't have the flag.
+    pub(crate) masks: [u8; 7],
+}
+
+impl ResetFlagRegister {
+    /// Returns the mask of the flag for `cause`, or `None` if this chip can't detect it.
+    #[inline]
+    pub const fn mask(&self, cause: ResetCause) -> Option<u8> {
+        match self.masks[cause as usize] {
+            0 => None,
+            mask => Some(mask),
+        }
+    }
+
+    /// Decodes a value read from this register.
+    #[inline]
+    pub const fn decode(&self, bits: u8) -> ResetFlags {
+        ResetFlags {
+            bits,
+            masks: self.masks,
+        }
+    }
+}
+
+/// Set of reset flags, as read from a chip's reset flag register.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResetFlags {
+    bits: u8,
+    masks: [u8; 7],
+}
+
+impl ResetFlags {
+    /// Raw value of the reset flag register.
+    #[inline]
+    pub const fn bits(&self) -> u8 {
+        self.bits
+    }
+
+    /// Check whether the flag for `cause` is set.
+    #[inline]
+    pub const fn contains(&self, cause: ResetCause) -> bool {
+        self.bits & self.masks[cause as usize] != 0
+    }
+
+    /// Returns the cause of the reset, i.e. the set flag with the highest priority (see
+    /// [`ResetCause::PRIORITY`]), or `None` if no flag is set.
+    pub fn cause(&self) -> Option<ResetCause> {
+        ResetCause::PRIORITY
+            .into_iter()
+            .find(|cause| self.contains(*cause))
+    }
+}
+
+// Copy of the reset flag register, made by the runtime before RAM initialization.  It lives in
+// `.noinit` so the initialization doesn't overwrite it.
+#[cfg(all(feature = "rt", avr_device_single_mcu, target_arch = "avr"))]
+#[link_section = ".noinit"]
+static mut RESET_FLAGS: core::mem::MaybeUninit<u8> = core::mem::MaybeUninit::uninit();
+
+// `.init1` runs before the stack pointer and the zero register are set up, so only scratch
+// registers are used here.  `MCUSR` flags are cleared by writing zero, `RSTFR` flags by writing
+// back the ones which are set.
+#[cfg(all(feature = "rt", avr_device_single_mcu, target_arch = "avr"))]
+core::arch::global_asm!(
+    ".pushsection .init1,\"ax\",@progbits",
+    "lds r24, {register}",
+    "sts {flags}, r24",
+    "mov r25, r24",
+    "andi r25, {keep}",
+    "sts {register}, r25",
+    ".popsection",
+    register = const crate::selected::RESET_FLAGS.address,
+    keep = const if crate::selected::RESET_FLAGS.clear_by_writing_one { 0xff } else { 0x00 },
+    flags = sym RESET_FLAGS,
+);
+
+/// Returns the reset flags which were set when the program started.
+///
+/// This function is only available with the `rt` feature and a single selected MCU.
+#[cfg(all(feature = "rt", avr_device_single_mcu))]
+#[inline]
+pub fn flags() -> ResetFlags {
+    cfg_if::cfg_if! {
+        if #[cfg(target_arch = "avr")] {
+            // SAFETY: The runtime initializes RESET_FLAGS before any Rust code runs.
+            let bits = unsafe { core::ptr::addr_of!(RESET_FLAGS).read_volatile().assume_init() };
+            crate::selected::RESET_FLAGS.decode(bits)
+        } else {
+            unimplemented!()
+        }
+    }
+}
+
+/// Returns the cause of the last reset.
+///
+/// This is `None` if no reset flag was set, e.g. because the program was started by a bootloader
+/// which cleared them, or jumped to the reset vector directly.
+///
+/// This function is only available with the `rt` feature and a single selected MCU.
+#[cfg(all(feature = "rt", avr_device_single_mcu))]
+#[inline]
+pub fn cause() -> Option<ResetCause> {
+    flags().cause()
+}
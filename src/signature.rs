@@ -0,0 +1,126 @@
+//! Device Signature
+//!
+//! Every AVR has a three byte signature identifying the chip.  Each chip's module has the expected
+//! signature as a `SIGNATURE` constant, which can be compared against the signature of the chip
+//! the program actually runs on, to refuse running on the wrong one:
+//!
+//! ```ignore
+//! #[avr_device::entry]
+//! fn main() -> ! {
+//!     avr_device::signature::assert_signature();
+//!     // ...
+//! }
+//! ```
+//!
+//! `read_signature()` and `assert_signature()` are only available when exactly one MCU feature is
+//! selected.
+
+#[cfg(target_arch = "avr")]
+use core::arch::asm;
+
+/// Access to the signature row of a chip.
+///
+/// Each chip's module has a `SIGNATURE_ROW` constant of this type, generated from the chip's
+/// vendor description.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignatureRow {
+    /// The signature row is mapped into the data space (`SIGROW` on newer chips).
+    Mapped {
+        /// Address of the first signature byte in the data space.
+        address: u16,
+    },
+    /// The signature row is read with `lpm` after writing `command` to `SPMCSR`.
+    Spm {
+        /// Address of `SPMCSR` in the data space.
+        spmcsr: u16,
+        /// Value of `SPMCSR` which enables reading the signature row (`SIGRD` and `SPMEN`).
+        command: u8,
+    },
+    /// The chip can't read its signature at runtime.
+    Unavailable,
+}
+
+impl SignatureRow {
+    /// Reads the signature from this signature row.
+    ///
+    /// Returns `None` if the chip can't read its signature at runtime.
+    ///
+    /// # Safety
+    ///
+    /// The signature row must be the one of the chip the program runs on.
+    #[inline]
+    pub unsafe fn read(&self) -> Option<[u8; 3]> {
+        match *self {
+            SignatureRow::Mapped { address } => {
+                let row = crate::register(address);
+                Some([
+                    row.read_volatile(),
+                    row.add(1).read_volatile(),
+                    row.add(2).read_volatile(),
+                ])
+            }
+            SignatureRow::Spm { spmcsr, command } => {
+                // The signature bytes are at the even addresses of the row.
+                let mut signature = [0; 3];
+                for (i, byte) in signature.iter_mut().enumerate() {
+                    *byte = read_spm_byte(spmcsr, command, i as u16 * 2);
+                }
+                Some(signature)
+            }
+            SignatureRow::Unavailable => None,
+        }
+    }
+}
+
+#[inline(always)]
+unsafe fn read_spm_byte(spmcsr: u16, command: u8, address: u16) -> u8 {
+    cfg_if::cfg_if! {
+        if #[cfg(target_arch = "avr")] {
+            // The `lpm` must follow within 3 cycles after writing the command, so interrupts must
+            // not be serviced in between.
+            crate::interrupt::free(|_| {
+                let byte;
+                asm!(
+                    "st X, {command}",
+                    "lpm {byte}, Z",
+                    command = in(reg) command,
+                    byte = out(reg) byte,
+                    in("X") spmcsr,
+                    in("Z") address,
+                    options(nostack),
+                );
+                byte
+            })
+        } else {
+            let _ = (spmcsr, command, address);
+            unimplemented!()
+        }
+    }
+}
+
+/// Reads the signature of the chip the program runs on.
+///
+/// Returns `None` if the chip can't read its signature at runtime.
+///
+/// This function is only available with a single selected MCU.
+#[cfg(avr_device_single_mcu)]
+#[inline]
+pub fn read_signature() -> Option<[u8; 3]> {
+    // SAFETY: The program was built for the selected MCU.
+    unsafe { crate::selected::SIGNATURE_ROW.read() }
+}
+
+/// Panics if the signature of the chip the program runs on doesn't match the selected MCU.
+///
+/// On chips which can't read their signature at runtime, this does nothing.
+///
+/// This function is only available with a single selected MCU.
+#[cfg(avr_device_single_mcu)]
+#[inline]
+pub fn assert_signature() {
+    if let Some(signature) = read_signature() {
+        if signature != crate::selected::SIGNATURE {
+            panic!("Program was built for a different chip");
+        }
+    }
+}
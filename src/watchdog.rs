@@ -0,0 +1,301 @@
+//! Watchdog Timer
+//!
+//! The watchdog resets the chip (or raises an interrupt) if the program doesn't feed it within
+//! the configured timeout.  Changing its configuration requires a timed sequence of writes (or a
+//! write protected by [configuration change protection](crate::ccp) on newer chips), which this
+//! module does in assembly:
+//!
+//! ```ignore
+//! use avr_device::watchdog::{self, Timeout};
+//!
+//! watchdog::start(Timeout::S1);
+//! loop {
+//!     // ...
+//!     watchdog::feed();
+//! }
+//! ```
+//!
+//! After a watchdog reset, classic chips keep the watchdog running with the shortest timeout, so
+//! it has to be disabled or reconfigured early on, e.g. in a [`#[pre_init]`](macro@crate::pre_init)
+//! function.  [`disable()`] clears the watchdog reset flag first, which is a prerequisite for
+//! disabling the watchdog on these chips.
+//!
+//! Each chip's module has a `WATCHDOG` constant describing its watchdog timer.  The functions of
+//! this module are only available when exactly one MCU feature is selected.
+
+#[cfg(all(target_arch = "avr", avr_device_single_mcu))]
+use core::arch::asm;
+
+/// A watchdog timeout.
+///
+/// The watchdog runs from an inaccurate internal oscillator, so actual timeouts vary with supply
+/// voltage and temperature.  Classic chips don't support [`Ms8`](Timeout::Ms8) and some don't
+/// support [`S4`](Timeout::S4) and [`S8`](Timeout::S8).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+pub enum Timeout {
+    /// About 8 ms.
+    Ms8,
+    /// About 16 ms.
+    Ms16,
+    /// About 32 ms.
+    Ms32,
+    /// About 64 ms.
+    Ms64,
+    /// About 125 ms.
+    Ms125,
+    /// About 250 ms.
+    Ms250,
+    /// About 500 ms.
+    Ms500,
+    /// About 1 s.
+    S1,
+    /// About 2 s.
+    S2,
+    /// About 4 s.
+    S4,
+    /// About 8 s.
+    S8,
+}
+
+/// What happens when the watchdog times out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+pub enum Mode {
+    /// The chip is reset.
+    Reset,
+    /// The `WDT` interrupt is raised.
+    Interrupt,
+    /// The `WDT` interrupt is raised on the first timeout and the chip is reset on the next one.
+    /// The interrupt handler can call [`configure()`] to stay in this mode.
+    InterruptAndReset,
+}
+
+/// Description of the watchdog timer of a chip.
+///
+/// Each chip's module has a `WATCHDOG` constant of this type, generated from the chip's vendor
+/// description.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchdogController {
+    /// Watchdog configured through `WDTCSR` (`WDTCR` on older chips), using a timed sequence
+    /// (classic megaAVR and tinyAVR chips).
+    Classic {
+        /// Address of `WDTCSR` in the data space.
+        wdtcsr: u16,
+        /// Mask of `WDCE` (`WDTOE` on older chips), zero if the register is protected by
+        /// configuration change protection instead.
+        change_enable: u8,
+        /// Mask of `WDE`.
+        enable: u8,
+        /// Mask of `WDIE`, zero if the watchdog can't raise an interrupt.
+        interrupt_enable: u8,
+        /// Mask of `WDIF`, zero if the watchdog can't raise an interrupt.
+        interrupt_flag: u8,
+        /// Mask of the `WDP` bits.
+        prescaler: u8,
+        /// Supported timeouts and their `WDP` bits.
+        timeouts: &'static [(Timeout, u8)],
+    },
+    /// Watchdog configured through the protected `WDT.CTRLA` (tinyAVR 0/1-series, megaAVR
+    /// 0-series and AVR Dx).
+    Ctrla {
+        /// Address of `WDT.CTRLA` in the data space.
+        ctrla: u16,
+        /// Address of `WDT.STATUS` in the data space.
+        status: u16,
+        /// Mask of `SYNCBUSY` in `WDT.STATUS`.
+        busy: u8,
+        /// Supported timeouts and their `PERIOD` values.
+        timeouts: &'static [(Timeout, u8)],
+    },
+}
+
+/// Starts the watchdog, resetting the chip unless it is fed within `timeout`.
+///
+/// Panics if the chip doesn't support the timeout.
+///
+/// This function is only available with a single selected MCU.
+#[cfg(avr_device_single_mcu)]
+#[inline]
+pub fn start(timeout: Timeout) {
+    configure(timeout, Mode::Reset)
+}
+
+/// Starts the watchdog with `timeout`, or changes its configuration if it is already running.
+///
+/// The watchdog is fed before the change.  In the interrupt modes, a pending watchdog interrupt
+/// is cleared.
+///
+/// Panics if the chip doesn't support the timeout, or the mode if it isn't [`Mode::Reset`].  The
+/// newer chips can only reset.
+///
+/// This function is only available with a single selected MCU.
+#[cfg(avr_device_single_mcu)]
+pub fn configure(timeout: Timeout, mode: Mode) {
+    let bits = |timeouts: &[(Timeout, u8)]| match timeouts.iter().find(|(t, _)| *t == timeout) {
+        Some(&(_, bits)) => bits,
+        None => panic!("This chip's watchdog doesn't support the timeout"),
+    };
+    match crate::selected::WATCHDOG {
+        WatchdogController::Classic {
+            enable,
+            interrupt_enable,
+            interrupt_flag,
+            timeouts,
+            ..
+        } => {
+            let mode = match mode {
+                Mode::Reset => enable,
+                Mode::Interrupt if interrupt_enable != 0 => interrupt_enable,
+                Mode::InterruptAndReset if interrupt_enable != 0 => interrupt_enable | enable,
+                _ => panic!("This chip's watchdog can't raise an interrupt"),
+            };
+            // Writing one to `WDIF` clears it.
+            let value = bits(timeouts) | mode | interrupt_flag;
+            // SAFETY: The value is valid for `WDTCSR`.
+            unsafe { write_classic(value) };
+        }
+        WatchdogController::Ctrla { timeouts, .. } => match mode {
+            // SAFETY: The window mode stays disabled.
+            Mode::Reset => unsafe { write_ctrla(bits(timeouts)) },
+            _ => panic!("This chip's watchdog can't raise an interrupt"),
+        },
+    }
+}
+
+/// Feeds the watchdog, restarting its timeout.
+///
+/// This is [`asm::wdr()`](crate::asm::wdr).
+#[inline(always)]
+pub fn feed() {
+    crate::asm::wdr()
+}
+
+/// Stops the watchdog.
+///
+/// On classic chips, this clears the watchdog reset flag.  On newer chips, the watchdog can't be
+/// stopped when it was locked, e.g. by the `WDTCFG` fuse.
+///
+/// This function is only available with a single selected MCU.
+#[cfg(avr_device_single_mcu)]
+pub fn disable() {
+    match crate::selected::WATCHDOG {
+        WatchdogController::Classic { interrupt_flag, .. } => {
+            // SAFETY: Clears all bits except for `WDIF`, which is cleared by writing one.
+            unsafe { write_classic(interrupt_flag) }
+        }
+        // SAFETY: A `PERIOD` of zero stops the watchdog.
+        WatchdogController::Ctrla { .. } => unsafe { write_ctrla(0) },
+    }
+}
+
+// Writes `value` to `WDTCSR` using the timed sequence.
+#[cfg(avr_device_single_mcu)]
+unsafe fn write_classic(value: u8) {
+    let WatchdogController::Classic {
+        wdtcsr,
+        change_enable,
+        enable,
+        ..
+    } = crate::selected::WATCHDOG
+    else {
+        unreachable!()
+    };
+    crate::interrupt::free(|_| {
+        crate::asm::wdr();
+        // The watchdog reset flag overrides `WDE`, so it has to be cleared first.
+        if value & enable == 0 {
+            let flags = crate::selected::RESET_FLAGS;
+            if let Some(mask) = flags.mask(crate::reset::ResetCause::Watchdog) {
+                let register = crate::register(flags.address);
+                match flags.clear_by_writing_one {
+                    true => register.write_volatile(mask),
+                    false => register.write_volatile(register.read_volatile() & !mask),
+                }
+            }
+        }
+
+        match (change_enable, crate::selected::CCP) {
+            (0, Some(ccp)) => {
+                crate::ccp::write(ccp, crate::register(wdtcsr), crate::ccp::IOREG, value)
+            }
+            _ => {
+                cfg_if::cfg_if! {
+                    if #[cfg(target_arch = "avr")] {
+                        // The new value must be written within four cycles after `WDCE`.
+                        asm!(
+                            "st X, {unlock}",
+                            "st X, {value}",
+                            unlock = in(reg) change_enable | enable,
+                            value = in(reg) value,
+                            in("X") wdtcsr,
+                            options(nostack, preserves_flags),
+                        );
+                    } else if #[cfg(feature = "host-mock")] {
+                        let wdtcsr = crate::register(wdtcsr);
+                        wdtcsr.write_volatile(change_enable | enable);
+                        wdtcsr.write_volatile(value);
+                    } else {
+                        let _ = wdtcsr;
+                        unimplemented!()
+                    }
+                }
+            }
+        }
+    })
+}
+
+// Writes `value` to the protected `WDT.CTRLA`, after the previous write was synchronized.
+#[cfg(avr_device_single_mcu)]
+unsafe fn write_ctrla(value: u8) {
+    let WatchdogController::Ctrla {
+        ctrla,
+        status,
+        busy,
+        ..
+    } = crate::selected::WATCHDOG
+    else {
+        unreachable!()
+    };
+    while crate::register(status).read_volatile() & busy != 0 {}
+    crate::interrupt::free(|_| {
+        crate::asm::wdr();
+        if let Some(ccp) = crate::selected::CCP {
+            crate::ccp::write(ccp, crate::register(ctrla), crate::ccp::IOREG, value);
+        }
+    })
+}
+
+#[cfg(all(test, feature = "host-mock", avr_device_single_mcu))]
+mod tests {
+    use super::*;
+    use crate::mock::{self, Event};
+
+    #[test]
+    fn start_and_disable() {
+        let mut registers = crate::selected::REGISTER_FILE.lock();
+        registers.reset();
+        mock::take_events();
+
+        start(Timeout::S1);
+        // Classic chips write one to `WDIF` to clear it.
+        let (register, timeouts, enable, flag) = match crate::selected::WATCHDOG {
+            WatchdogController::Classic {
+                wdtcsr,
+                enable,
+                interrupt_flag,
+                timeouts,
+                ..
+            } => (wdtcsr, timeouts, enable, interrupt_flag),
+            WatchdogController::Ctrla {
+                ctrla, timeouts, ..
+            } => (ctrla, timeouts, 0, 0),
+        };
+        let &(_, bits) = timeouts.iter().find(|(t, _)| *t == Timeout::S1).unwrap();
+        assert_eq!(registers.get(register), bits | enable | flag);
+        assert_eq!(mock::take_events(), [Event::WatchdogReset]);
+
+        disable();
+        assert_eq!(registers.get(register), flag);
+    }
+}